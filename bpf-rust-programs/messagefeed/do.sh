@@ -15,6 +15,7 @@ Supported actions:
     clean
     test
     clippy
+    fuzz <target>
     fmt
 
 EOF
@@ -52,6 +53,11 @@ perform_action() {
         echo "clippy"
         cargo +nightly clippy
         ;;
+    fuzz)
+        echo "fuzz $2"
+        mkdir -p fuzz/corpus/"$2"
+        cargo +nightly fuzz run "$2" fuzz/corpus/"$2" fuzz/seeds/"$2"
+        ;;
     fmt)
         echo "formatting"
         cargo fmt
@@ -70,4 +76,4 @@ perform_action() {
 
 set -e
 
-perform_action "$1"
+perform_action "$@"
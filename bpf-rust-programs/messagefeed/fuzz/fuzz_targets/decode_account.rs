@@ -0,0 +1,21 @@
+//! Decodes account data as user and message accounts
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use messagefeed::{MessageAccountData, UserAccountData, MESSAGE_SIZE};
+
+fuzz_target!(|data: &[u8]| {
+    let mut user_data = data.to_vec();
+    if let Ok(user) = UserAccountData::new(&mut user_data) {
+        let _ = *user.banned != 0;
+    }
+
+    let mut message_data = data.to_vec();
+    if let Ok(message) = MessageAccountData::new(&mut message_data) {
+        let fields_len = match message.version {
+            1 => 96,
+            _ => MESSAGE_SIZE,
+        };
+        assert_eq!(message.text.len(), data.len() - fields_len);
+    }
+});
@@ -0,0 +1,126 @@
+//! Runs sequences of instructions against a fixed set of synthetic accounts
+//!
+//! Each step is encoded as `account count, account indices, data length, data`.
+//! An account index selects from the accounts below, its high bit marks the account
+//! as a signer. Failed instructions are rolled back like the runtime would.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use messagefeed::{process_instruction, MESSAGE_SIZE, USER_SIZE};
+use solana_sdk::{account_info::AccountInfo, pubkey::Pubkey};
+
+const SIGNER: u8 = 0x80;
+// Sizes of accounts written before layouts were versioned
+const LEGACY_USER_SIZE: usize = 1 + 32;
+const LEGACY_MESSAGE_SIZE: usize = 3 * 32;
+
+struct Account {
+    key: Pubkey,
+    lamports: u64,
+    data: Vec<u8>,
+    owner: Pubkey,
+}
+
+impl Account {
+    fn new(seed: u8, owner: &Pubkey, size: usize) -> Self {
+        Self {
+            key: Pubkey::new(&[seed; 32]),
+            lamports: 1,
+            data: vec![0; size],
+            owner: *owner,
+        }
+    }
+
+    fn info(&mut self) -> AccountInfo<'_> {
+        AccountInfo::new(
+            &self.key,
+            false,
+            true,
+            &mut self.lamports,
+            &mut self.data,
+            &self.owner,
+            false,
+            0,
+        )
+    }
+}
+
+/// Two users and messages with room for 0, 4 and 16 bytes of text, followed by
+/// accounts sized for the unversioned layout
+fn accounts(program_id: &Pubkey) -> Vec<Account> {
+    vec![
+        Account::new(0, program_id, USER_SIZE),
+        Account::new(1, program_id, USER_SIZE),
+        Account::new(2, program_id, MESSAGE_SIZE),
+        Account::new(3, program_id, MESSAGE_SIZE + 4),
+        Account::new(4, program_id, MESSAGE_SIZE + 4),
+        Account::new(5, program_id, MESSAGE_SIZE + 16),
+        Account::new(6, program_id, LEGACY_USER_SIZE),
+        Account::new(7, program_id, LEGACY_MESSAGE_SIZE + 4),
+    ]
+}
+
+struct Step<'a> {
+    accounts: &'a [u8],
+    data: &'a [u8],
+}
+
+fn next_step<'a>(input: &mut &'a [u8]) -> Option<Step<'a>> {
+    let (&account_count, rest) = input.split_first()?;
+    if rest.len() < account_count as usize {
+        return None;
+    }
+    let (accounts, rest) = rest.split_at(account_count as usize);
+    let (&data_len, rest) = rest.split_first()?;
+    if rest.len() < data_len as usize {
+        return None;
+    }
+    let (data, rest) = rest.split_at(data_len as usize);
+    *input = rest;
+    Some(Step { accounts, data })
+}
+
+fuzz_target!(|input: &[u8]| {
+    let program_id = Pubkey::new(&[0xff; 32]);
+    let mut accounts = accounts(&program_id);
+    let infos: Vec<_> = accounts.iter_mut().map(Account::info).collect();
+
+    let mut input = input;
+    while let Some(step) = next_step(&mut input) {
+        let indices: Vec<_> = step
+            .accounts
+            .iter()
+            .map(|index| usize::from(index & !SIGNER) % infos.len())
+            .collect();
+        // The runtime shares one borrow between duplicate accounts, which the
+        // program does not expect, so those instructions are skipped
+        if (1..indices.len()).any(|i| indices[..i].contains(&indices[i])) {
+            continue;
+        }
+
+        let snapshot: Vec<_> = infos
+            .iter()
+            .map(|info| (info.lamports(), info.data.borrow().to_vec()))
+            .collect();
+
+        let instruction_accounts: Vec<_> = step
+            .accounts
+            .iter()
+            .zip(indices)
+            .map(|(index, i)| {
+                let mut info = infos[i].clone();
+                info.is_signer = index & SIGNER != 0;
+                info
+            })
+            .collect();
+        if process_instruction(&program_id, &instruction_accounts, step.data).is_err() {
+            for (info, (lamports, data)) in infos.iter().zip(snapshot) {
+                **info.lamports.borrow_mut() = lamports;
+                info.data.borrow_mut().copy_from_slice(&data);
+            }
+        }
+    }
+});
+
+// Pulls in the stubs required for `info!()`
+solana_sdk_bpf_test::stubs!();
@@ -22,6 +22,12 @@ pub enum MessageFeedError {
     NextMessageExists,
     #[error("Creator mismatch")]
     CreatorMismatch,
+    #[error("Account layout version is unsupported")]
+    UnsupportedVersion,
+    #[error("Account is already migrated")]
+    AccountAlreadyMigrated,
+    #[error("Migrated account links do not match")]
+    InvalidMigrationLinks,
 }
 impl From<MessageFeedError> for ProgramError {
     fn from(e: MessageFeedError) -> Self {
@@ -36,58 +42,188 @@ impl<T> DecodeError<T> for MessageFeedError {
 
 type PubkeyData = [u8; 32];
 
-struct UserAccountData<'a> {
-    pub banned: &'a mut bool,
+/// Starts the data of accounts written with a layout version, followed by the version.
+/// Accounts from before versioning start straight with their fields. User accounts
+/// start with the banned flag, which is 0 or 1 and never the tag. Messages start
+/// with the key of the next message, so a legacy message is misread as versioned
+/// if that key begins with the tag and a supported version. Keys are random, it
+/// takes grinding a key with those 8 bytes to get one.
+const VERSION_TAG: &[u8; 7] = b"msgfeed";
+const HEADER_SIZE: usize = VERSION_TAG.len() + 1;
+
+/// Layout version of new accounts, unversioned accounts are version 1
+pub const LAYOUT_VERSION: u8 = 2;
+
+/// Tagged instructions start with `VERSION_TAG` and this version, then the
+/// instruction tag. Data without that prefix is read the way clients from before
+/// instructions were tagged encode it, version 1: empty data inits a user and
+/// anything else is the text of a message. Text that starts with the prefix has
+/// to be posted tagged.
+pub const INSTRUCTION_VERSION: u8 = 2;
+
+pub const USER_SIZE: usize = HEADER_SIZE + 1 + size_of::<PubkeyData>();
+/// Message accounts are sized to hold their text after this
+pub const MESSAGE_SIZE: usize = HEADER_SIZE + 3 * size_of::<PubkeyData>();
+
+/// Splits account data into its layout version and its fields
+fn split_header(data: &mut [u8]) -> Result<(u8, &mut [u8]), ProgramError> {
+    if data.len() < HEADER_SIZE || data[..VERSION_TAG.len()] != VERSION_TAG[..] {
+        return Ok((1, data));
+    }
+    let (header, fields) = data.split_at_mut(HEADER_SIZE);
+    match header[VERSION_TAG.len()] {
+        version @ 2..=LAYOUT_VERSION => Ok((version, fields)),
+        _ => Err(MessageFeedError::UnsupportedVersion.into()),
+    }
+}
+
+/// Writes the header of the latest layout version and returns the space for the fields
+fn write_header(data: &mut [u8]) -> Result<&mut [u8], ProgramError> {
+    if data.len() < HEADER_SIZE {
+        return Err(ProgramError::AccountDataTooSmall);
+    }
+    let (header, fields) = data.split_at_mut(HEADER_SIZE);
+    header[..VERSION_TAG.len()].copy_from_slice(VERSION_TAG);
+    header[VERSION_TAG.len()] = LAYOUT_VERSION;
+    Ok(fields)
+}
+
+pub struct UserAccountData<'a> {
+    pub version: u8,
+    pub banned: &'a mut u8,
     pub creator: &'a mut PubkeyData,
 }
 impl<'a> UserAccountData<'a> {
-    fn new(data: &'a mut [u8]) -> Self {
+    pub fn new(data: &'a mut [u8]) -> Result<Self, ProgramError> {
+        let (version, fields) = split_header(data)?;
+        Self::from_fields(version, fields)
+    }
+
+    /// Writes a user account at the latest layout version
+    pub fn init(data: &'a mut [u8]) -> Result<Self, ProgramError> {
+        if data.len() < USER_SIZE {
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+        Self::from_fields(LAYOUT_VERSION, write_header(data)?)
+    }
+
+    fn from_fields(version: u8, data: &'a mut [u8]) -> Result<Self, ProgramError> {
+        if data.len() < 1 + size_of::<PubkeyData>() {
+            return Err(ProgramError::AccountDataTooSmall);
+        }
         let (banned, creator) = data.split_at_mut(1);
-        Self {
-            banned: unsafe { &mut *(&mut banned[0] as *mut u8 as *mut bool) },
+        Ok(Self {
+            version,
+            banned: &mut banned[0],
             creator: array_mut_ref!(creator, 0, size_of::<PubkeyData>()),
-        }
+        })
     }
 }
 
-struct MessageAccountData<'a> {
+pub struct MessageAccountData<'a> {
+    pub version: u8,
     pub next_message: &'a mut PubkeyData,
     pub from: &'a mut PubkeyData,
     pub creator: &'a mut PubkeyData,
     pub text: &'a mut [u8],
 }
 impl<'a> MessageAccountData<'a> {
-    fn new(data: &'a mut [u8]) -> Self {
+    pub fn new(data: &'a mut [u8]) -> Result<Self, ProgramError> {
+        let (version, fields) = split_header(data)?;
+        Self::from_fields(version, fields)
+    }
+
+    /// Writes a message account at the latest layout version
+    pub fn init(data: &'a mut [u8]) -> Result<Self, ProgramError> {
+        if data.len() < MESSAGE_SIZE {
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+        Self::from_fields(LAYOUT_VERSION, write_header(data)?)
+    }
+
+    fn from_fields(version: u8, data: &'a mut [u8]) -> Result<Self, ProgramError> {
+        if data.len() < 3 * size_of::<PubkeyData>() {
+            return Err(ProgramError::AccountDataTooSmall);
+        }
         let (next_message, rest) = data.split_at_mut(size_of::<PubkeyData>());
         let (from, rest) = rest.split_at_mut(size_of::<PubkeyData>());
         let (creator, text) = rest.split_at_mut(size_of::<PubkeyData>());
-        Self {
+        Ok(Self {
+            version,
             next_message: array_mut_ref!(next_message, 0, size_of::<PubkeyData>()),
             from: array_mut_ref!(from, 0, size_of::<PubkeyData>()),
             creator: array_mut_ref!(creator, 0, size_of::<PubkeyData>()),
             text,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AccountKind {
+    User,
+    Message,
+}
+
+/// Instructions are tagged by the byte after the `INSTRUCTION_VERSION` prefix
+#[derive(Debug, PartialEq)]
+pub enum MessageFeedInstruction<'a> {
+    /// Sets the chain a user may post to, identified by its first message
+    ///
+    /// Accounts: [signer] user, [signer] first message
+    InitUser,
+    /// Posts a message, replies are linked from the previous message and may ban a user
+    ///
+    /// Accounts: [signer] user, [signer] new message, previous message, user to ban
+    PostMessage(&'a [u8]),
+    /// Upgrades an account to the latest layout version. Accounts without room
+    /// for the new layout move to a new account, a message being moved is
+    /// relinked from the previous message and signed by the user who posted it,
+    /// a user being moved must sign.
+    ///
+    /// Accounts: account, [signer] new account, previous message, [signer] message user
+    Migrate(AccountKind),
+}
+impl<'a> MessageFeedInstruction<'a> {
+    pub fn unpack(data: &'a [u8]) -> Result<Self, ProgramError> {
+        if data.is_empty() {
+            return Ok(MessageFeedInstruction::InitUser);
         }
+        if data.len() < HEADER_SIZE || data[..VERSION_TAG.len()] != VERSION_TAG[..] {
+            return Ok(MessageFeedInstruction::PostMessage(data));
+        }
+        let (header, data) = data.split_at(HEADER_SIZE);
+        if header[VERSION_TAG.len()] != INSTRUCTION_VERSION {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        let (tag, rest) = data
+            .split_first()
+            .ok_or(ProgramError::InvalidInstructionData)?;
+        Ok(match (tag, rest) {
+            (0, []) => MessageFeedInstruction::InitUser,
+            (1, text) => MessageFeedInstruction::PostMessage(text),
+            (2, [0]) => MessageFeedInstruction::Migrate(AccountKind::User),
+            (2, [1]) => MessageFeedInstruction::Migrate(AccountKind::Message),
+            _ => return Err(ProgramError::InvalidInstructionData),
+        })
     }
 }
 
 entrypoint!(process_instruction);
-fn process_instruction(
+pub fn process_instruction(
     _program_id: &Pubkey,
     accounts: &[AccountInfo],
     instruction_data: &[u8],
 ) -> ProgramResult {
     info!("message feed entrypoint");
 
-    let account_info_iter = &mut accounts.iter();
-
-    let user_account = next_account_info(account_info_iter)?;
-    let mut user_data = user_account.data.borrow_mut();
-    let user_data = UserAccountData::new(&mut user_data);
-
-    let message_account = next_account_info(account_info_iter)?;
-    let mut new_message_data = message_account.data.borrow_mut();
-    let new_message_data = MessageAccountData::new(&mut new_message_data);
+    match MessageFeedInstruction::unpack(instruction_data)? {
+        MessageFeedInstruction::InitUser => init_user(accounts),
+        MessageFeedInstruction::PostMessage(text) => post_message(accounts, text),
+        MessageFeedInstruction::Migrate(kind) => migrate(accounts, kind),
+    }
+}
 
+fn expect_signers(user_account: &AccountInfo, message_account: &AccountInfo) -> ProgramResult {
     if !user_account.is_signer {
         info!("Error: not signed by key 0");
         return Err(ProgramError::MissingRequiredSignature);
@@ -96,64 +232,630 @@ fn process_instruction(
         info!("Error: not signed by key 1");
         return Err(ProgramError::MissingRequiredSignature);
     }
+    Ok(())
+}
+
+fn init_user(accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let user_account = next_account_info(account_info_iter)?;
+    let message_account = next_account_info(account_info_iter)?;
+    expect_signers(user_account, message_account)?;
+
+    let mut user_data = user_account.data.borrow_mut();
+    if *UserAccountData::new(&mut user_data)?.banned != 0 {
+        info!("Error: user is banned");
+        return Err(MessageFeedError::BannedUser.into());
+    }
+
+    let user_data = UserAccountData::init(&mut user_data)?;
+    user_data
+        .creator
+        .clone_from_slice(message_account.key.as_ref());
+
+    info!("Success");
+    Ok(())
+}
+
+fn post_message(accounts: &[AccountInfo], text: &[u8]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let user_account = next_account_info(account_info_iter)?;
+    let message_account = next_account_info(account_info_iter)?;
+    expect_signers(user_account, message_account)?;
 
-    if *user_data.banned {
+    let mut user_data = user_account.data.borrow_mut();
+    let user_data = UserAccountData::new(&mut user_data)?;
+    if *user_data.banned != 0 {
         info!("Error: user is banned");
         return Err(MessageFeedError::BannedUser.into());
     }
 
-    // No instruction data means that a new user account should be initialized
-    if instruction_data.is_empty() {
-        user_data
-            .creator
+    // Write the message text into new_message_data
+    let mut new_message_data = message_account.data.borrow_mut();
+    let new_message_data = MessageAccountData::init(&mut new_message_data)?;
+    if new_message_data.text.len() != text.len() {
+        info!("Error: message text does not fit account 1");
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    new_message_data.text.clone_from_slice(text);
+
+    // Save the pubkey of who posted the message
+    new_message_data
+        .from
+        .clone_from_slice(user_account.key.as_ref());
+
+    if let Ok(existing_message_account) = next_account_info(account_info_iter) {
+        let mut existing_message_data = existing_message_account.data.borrow_mut();
+        let existing_message_data = MessageAccountData::new(&mut existing_message_data)?;
+
+        if existing_message_data.next_message != &[0; size_of::<PubkeyData>()] {
+            info!("Error: account 1 already has a next_message");
+            return Err(MessageFeedError::NextMessageExists.into());
+        }
+
+        // Link the new_message to the existing_message
+        existing_message_data
+            .next_message
             .clone_from_slice(message_account.key.as_ref());
-    } else {
-        // Write the message text into new_message_data
-        new_message_data.text.clone_from_slice(instruction_data);
 
-        // Save the pubkey of who posted the message
+        // Check if a user should be banned
+        if let Ok(ban_user_account) = next_account_info(account_info_iter) {
+            let mut ban_user_data = ban_user_account.data.borrow_mut();
+            let ban_user_data = UserAccountData::new(&mut ban_user_data)?;
+            *ban_user_data.banned = 1;
+        }
+
+        // Propagate the chain creator to the new message
+        new_message_data
+            .creator
+            .clone_from_slice(existing_message_data.creator.as_ref());
+    } else {
+        // This is the first message in the chain, it is the "creator"
         new_message_data
-            .from
-            .clone_from_slice(user_account.key.as_ref());
+            .creator
+            .clone_from_slice(message_account.key.as_ref());
+    }
 
-        if let Ok(existing_message_account) = next_account_info(account_info_iter) {
-            let mut existing_message_data = existing_message_account.data.borrow_mut();
-            let existing_message_data = MessageAccountData::new(&mut existing_message_data);
+    if user_data.creator != new_message_data.creator {
+        info!("user_data/new_message_data creator mismatch");
+        return Err(MessageFeedError::CreatorMismatch.into());
+    }
 
-            if existing_message_data.next_message != &[0; size_of::<PubkeyData>()] {
-                info!("Error: account 1 already has a next_message");
-                return Err(MessageFeedError::NextMessageExists.into());
-            }
+    info!("Success");
+    Ok(())
+}
 
-            // Link the new_message to the existing_message
-            existing_message_data
-                .next_message
-                .clone_from_slice(message_account.key.as_ref());
+fn migrate(accounts: &[AccountInfo], kind: AccountKind) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let account = next_account_info(account_info_iter)?;
+    let mut data = account.data.borrow_mut();
 
-            // Check if a user should be banned
-            if let Ok(ban_user_account) = next_account_info(account_info_iter) {
-                let mut ban_user_data = ban_user_account.data.borrow_mut();
-                let ban_user_data = UserAccountData::new(&mut ban_user_data);
-                *ban_user_data.banned = true;
+    // Fields keep their layout across versions so far, message text runs to the
+    // end of the data
+    let (header_len, fields_len, is_first_message, from) = {
+        let (version, fields) = split_header(&mut data)?;
+        if version == LAYOUT_VERSION {
+            info!("Error: account 0 is already migrated");
+            return Err(MessageFeedError::AccountAlreadyMigrated.into());
+        }
+        let header_len = if version == 1 { 0 } else { HEADER_SIZE };
+        match kind {
+            AccountKind::User => {
+                UserAccountData::from_fields(version, fields)?;
+                (
+                    header_len,
+                    1 + size_of::<PubkeyData>(),
+                    false,
+                    [0; size_of::<PubkeyData>()],
+                )
             }
+            AccountKind::Message => {
+                let message = MessageAccountData::from_fields(version, fields)?;
+                let fields_len = 3 * size_of::<PubkeyData>() + message.text.len();
+                (
+                    header_len,
+                    fields_len,
+                    message.creator == account.key.as_ref(),
+                    *message.from,
+                )
+            }
+        }
+    };
+    let fields = header_len..header_len + fields_len;
+
+    if HEADER_SIZE + fields_len <= data.len() {
+        data.copy_within(fields, HEADER_SIZE);
+        write_header(&mut data)?;
+        info!("Success");
+        return Ok(());
+    }
+
+    let new_account = next_account_info(account_info_iter)?;
+    if !new_account.is_signer {
+        info!("Error: not signed by key 1");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    let mut new_data = new_account.data.borrow_mut();
+    if new_data.iter().any(|&byte| byte != 0) {
+        info!("Error: account 1 is already in use");
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
 
-            // Propagate the chain creator to the new message
-            new_message_data
-                .creator
-                .clone_from_slice(existing_message_data.creator.as_ref());
-        } else {
-            // This is the first message in the chain, it is the "creator"
-            new_message_data
-                .creator
-                .clone_from_slice(message_account.key.as_ref());
+    match kind {
+        AccountKind::User => {
+            // The user's key moves with their account
+            if !account.is_signer {
+                info!("Error: not signed by key 0");
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            if new_data.len() < HEADER_SIZE + fields_len {
+                return Err(ProgramError::AccountDataTooSmall);
+            }
         }
+        AccountKind::Message => {
+            // The text fills the account, and a chain is named after its first
+            // message so that one can't move
+            if new_data.len() != HEADER_SIZE + fields_len {
+                info!("Error: message text does not fit account 1");
+                return Err(ProgramError::InvalidAccountData);
+            }
+            if is_first_message {
+                info!("Error: the first message of a chain can't move");
+                return Err(MessageFeedError::InvalidMigrationLinks.into());
+            }
+
+            let previous_message_account = next_account_info(account_info_iter)?;
+            let mut previous_message_data = previous_message_account.data.borrow_mut();
+            let previous_message_data = MessageAccountData::new(&mut previous_message_data)?;
+            if previous_message_data.next_message != account.key.as_ref() {
+                info!("Error: account 2 does not link to account 0");
+                return Err(MessageFeedError::InvalidMigrationLinks.into());
+            }
 
-        if user_data.creator != new_message_data.creator {
-            info!("user_data/new_message_data creator mismatch");
-            return Err(MessageFeedError::CreatorMismatch.into());
+            // Moving a message is up to the user who posted it, as posting was
+            let user_account = next_account_info(account_info_iter)?;
+            if !user_account.is_signer || user_account.key.as_ref() != from {
+                info!("Error: not signed by the user who posted account 0");
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+
+            previous_message_data
+                .next_message
+                .clone_from_slice(new_account.key.as_ref());
         }
     }
 
+    write_header(&mut new_data)?[..fields_len].copy_from_slice(&data[fields]);
+
+    // Empty the old account into the new one
+    let lamports = new_account
+        .lamports()
+        .checked_add(account.lamports())
+        .ok_or(ProgramError::InvalidArgument)?;
+    **new_account.lamports.borrow_mut() = lamports;
+    **account.lamports.borrow_mut() = 0;
+    for byte in data.iter_mut() {
+        *byte = 0;
+    }
+
     info!("Success");
     Ok(())
 }
+
+// Pulls in the stubs required for `info!()`
+#[cfg(test)]
+solana_sdk_bpf_test::stubs!();
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct TestAccount {
+        key: Pubkey,
+        is_signer: bool,
+        lamports: u64,
+        data: Vec<u8>,
+        owner: Pubkey,
+    }
+
+    impl TestAccount {
+        fn new(seed: u8, size: usize) -> Self {
+            Self {
+                key: Pubkey::new(&[seed; 32]),
+                is_signer: true,
+                lamports: 1,
+                data: vec![0; size],
+                owner: program_id(),
+            }
+        }
+
+        fn user(seed: u8) -> Self {
+            Self::new(seed, USER_SIZE)
+        }
+
+        fn message(seed: u8, text: &str) -> Self {
+            Self::new(seed, MESSAGE_SIZE + text.len())
+        }
+
+        fn info(&mut self) -> AccountInfo<'_> {
+            AccountInfo::new(
+                &self.key,
+                self.is_signer,
+                true,
+                &mut self.lamports,
+                &mut self.data,
+                &self.owner,
+                false,
+                0,
+            )
+        }
+
+        fn is_banned(&mut self) -> bool {
+            *UserAccountData::new(&mut self.data).unwrap().banned != 0
+        }
+
+        fn creator(&mut self) -> Pubkey {
+            Pubkey::new(UserAccountData::new(&mut self.data).unwrap().creator)
+        }
+
+        fn version(&mut self) -> u8 {
+            split_header(&mut self.data).unwrap().0
+        }
+
+        /// Rewrites the account without its header, as written before layouts were versioned
+        fn downgrade(&mut self) {
+            self.data.drain(..HEADER_SIZE);
+        }
+    }
+
+    fn program_id() -> Pubkey {
+        Pubkey::new(&[0xff; 32])
+    }
+
+    /// Instruction data in the tagged format
+    fn tagged(tag: u8, payload: &[u8]) -> Vec<u8> {
+        [&VERSION_TAG[..], &[INSTRUCTION_VERSION, tag], payload].concat()
+    }
+
+    fn process(accounts: &mut [&mut TestAccount], data: &[u8]) -> ProgramResult {
+        let infos: Vec<_> = accounts.iter_mut().map(|account| account.info()).collect();
+        process_instruction(&program_id(), &infos, data)
+    }
+
+    /// Sets the chain a user may post to, identified by its first message
+    fn init_user(user: &mut TestAccount, first_message: &mut TestAccount) -> ProgramResult {
+        process(&mut [user, first_message], &tagged(0, &[]))
+    }
+
+    fn post(
+        user: &mut TestAccount,
+        message: &mut TestAccount,
+        text: &str,
+        existing_message: Option<&mut TestAccount>,
+    ) -> ProgramResult {
+        let data = tagged(1, text.as_bytes());
+        match existing_message {
+            Some(existing_message) => process(&mut [user, message, existing_message], &data),
+            None => process(&mut [user, message], &data),
+        }
+    }
+
+    fn migrate(accounts: &mut [&mut TestAccount], kind: AccountKind) -> ProgramResult {
+        process(accounts, &tagged(2, &[kind as u8]))
+    }
+
+    #[test]
+    pub fn init_user_account() {
+        let mut user = TestAccount::user(1);
+        let mut first_message = TestAccount::message(2, "hello");
+
+        first_message.is_signer = false;
+        assert_eq!(
+            init_user(&mut user, &mut first_message),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+
+        first_message.is_signer = true;
+        init_user(&mut user, &mut first_message).unwrap();
+        assert_eq!(user.creator(), first_message.key);
+        assert!(!user.is_banned());
+    }
+
+    #[test]
+    pub fn first_post() {
+        let mut user = TestAccount::user(1);
+        let mut message = TestAccount::message(2, "hello");
+        init_user(&mut user, &mut message).unwrap();
+
+        assert_eq!(
+            post(&mut user, &mut message, "hi", None),
+            Err(ProgramError::InvalidInstructionData)
+        );
+        post(&mut user, &mut message, "hello", None).unwrap();
+
+        let message_data = MessageAccountData::new(&mut message.data).unwrap();
+        assert_eq!(message_data.text, b"hello");
+        assert_eq!(message_data.from, &[1; 32]);
+        assert_eq!(message_data.creator, &[2; 32]);
+        assert_eq!(message_data.next_message, &[0; 32]);
+    }
+
+    #[test]
+    pub fn reply() {
+        let mut creator = TestAccount::user(1);
+        let mut first_message = TestAccount::message(2, "hello");
+        init_user(&mut creator, &mut first_message).unwrap();
+        post(&mut creator, &mut first_message, "hello", None).unwrap();
+
+        let mut user = TestAccount::user(3);
+        let mut reply = TestAccount::message(4, "hi");
+        init_user(&mut user, &mut first_message).unwrap();
+        post(&mut user, &mut reply, "hi", Some(&mut first_message)).unwrap();
+
+        let first_message_data = MessageAccountData::new(&mut first_message.data).unwrap();
+        assert_eq!(first_message_data.next_message, &[4; 32]);
+        let reply_data = MessageAccountData::new(&mut reply.data).unwrap();
+        assert_eq!(reply_data.from, &[3; 32]);
+        assert_eq!(reply_data.creator, &[2; 32]);
+        assert_eq!(reply_data.next_message, &[0; 32]);
+
+        let mut other_reply = TestAccount::message(5, "hey");
+        assert_eq!(
+            post(&mut user, &mut other_reply, "hey", Some(&mut first_message)),
+            Err(MessageFeedError::NextMessageExists.into())
+        );
+        post(&mut user, &mut other_reply, "hey", Some(&mut reply)).unwrap();
+    }
+
+    #[test]
+    pub fn creator_mismatch() {
+        let mut creator = TestAccount::user(1);
+        let mut first_message = TestAccount::message(2, "hello");
+        init_user(&mut creator, &mut first_message).unwrap();
+
+        // Starting a new chain requires a user created for it
+        let mut other_chain = TestAccount::message(3, "hello");
+        assert_eq!(
+            post(&mut creator, &mut other_chain, "hello", None),
+            Err(MessageFeedError::CreatorMismatch.into())
+        );
+
+        post(&mut creator, &mut first_message, "hello", None).unwrap();
+        let mut user = TestAccount::user(4);
+        let mut reply = TestAccount::message(5, "hi");
+        init_user(&mut user, &mut other_chain).unwrap();
+        assert_eq!(
+            post(&mut user, &mut reply, "hi", Some(&mut first_message)),
+            Err(MessageFeedError::CreatorMismatch.into())
+        );
+    }
+
+    #[test]
+    pub fn ban_user() {
+        let mut creator = TestAccount::user(1);
+        let mut first_message = TestAccount::message(2, "hello");
+        init_user(&mut creator, &mut first_message).unwrap();
+        post(&mut creator, &mut first_message, "hello", None).unwrap();
+
+        let mut user = TestAccount::user(3);
+        init_user(&mut user, &mut first_message).unwrap();
+
+        // Replies may name a user to ban
+        let mut reply = TestAccount::message(4, "banned");
+        process(
+            &mut [&mut creator, &mut reply, &mut first_message, &mut user],
+            &tagged(1, b"banned"),
+        )
+        .unwrap();
+        assert!(user.is_banned());
+        assert!(!creator.is_banned());
+
+        let mut next_reply = TestAccount::message(5, "hi");
+        assert_eq!(
+            post(&mut user, &mut next_reply, "hi", Some(&mut reply)),
+            Err(MessageFeedError::BannedUser.into())
+        );
+        assert_eq!(
+            init_user(&mut user, &mut first_message),
+            Err(MessageFeedError::BannedUser.into())
+        );
+    }
+
+    #[test]
+    pub fn unpack_instruction() {
+        assert_eq!(
+            MessageFeedInstruction::unpack(&tagged(0, &[])),
+            Ok(MessageFeedInstruction::InitUser)
+        );
+        assert_eq!(
+            MessageFeedInstruction::unpack(&tagged(1, b"hi")),
+            Ok(MessageFeedInstruction::PostMessage(b"hi"))
+        );
+        assert_eq!(
+            MessageFeedInstruction::unpack(&tagged(2, &[1])),
+            Ok(MessageFeedInstruction::Migrate(AccountKind::Message))
+        );
+        for data in [&[0, 0][..], &[2], &[2, 2], &[3]].iter() {
+            assert_eq!(
+                MessageFeedInstruction::unpack(&tagged(data[0], &data[1..])),
+                Err(ProgramError::InvalidInstructionData)
+            );
+        }
+        let mut unknown_version = tagged(0, &[]);
+        unknown_version[VERSION_TAG.len()] = 3;
+        assert_eq!(
+            MessageFeedInstruction::unpack(&unknown_version),
+            Err(ProgramError::InvalidInstructionData)
+        );
+        assert_eq!(
+            MessageFeedInstruction::unpack(&tagged(0, &[])[..HEADER_SIZE]),
+            Err(ProgramError::InvalidInstructionData)
+        );
+
+        // Untagged data is read as clients from before instructions were tagged send it
+        assert_eq!(
+            MessageFeedInstruction::unpack(&[]),
+            Ok(MessageFeedInstruction::InitUser)
+        );
+        for text in [&b"hi"[..], b"\x01hi", b"msgfeed"].iter() {
+            assert_eq!(
+                MessageFeedInstruction::unpack(text),
+                Ok(MessageFeedInstruction::PostMessage(text))
+            );
+        }
+    }
+
+    #[test]
+    pub fn untagged_instructions() {
+        let mut user = TestAccount::user(1);
+        let mut first_message = TestAccount::message(2, "hello");
+        process(&mut [&mut user, &mut first_message], &[]).unwrap();
+        assert_eq!(user.creator(), first_message.key);
+
+        process(&mut [&mut user, &mut first_message], b"hello").unwrap();
+        let message = MessageAccountData::new(&mut first_message.data).unwrap();
+        assert_eq!(message.text, b"hello");
+        assert_eq!(message.from, user.key.as_ref());
+    }
+
+    #[test]
+    pub fn versioned_accounts() {
+        let mut user = TestAccount::user(1);
+        let mut message = TestAccount::message(2, "hello");
+        init_user(&mut user, &mut message).unwrap();
+        post(&mut user, &mut message, "hello", None).unwrap();
+        assert_eq!(&user.data[..VERSION_TAG.len()], VERSION_TAG);
+        assert_eq!(user.version(), LAYOUT_VERSION);
+        assert_eq!(message.version(), LAYOUT_VERSION);
+
+        message.data[VERSION_TAG.len()] = LAYOUT_VERSION + 1;
+        assert_eq!(
+            MessageAccountData::new(&mut message.data).err(),
+            Some(MessageFeedError::UnsupportedVersion.into())
+        );
+    }
+
+    #[test]
+    pub fn migrate_user_in_place() {
+        let mut user = TestAccount::user(1);
+        let mut message = TestAccount::message(2, "hello");
+        init_user(&mut user, &mut message).unwrap();
+        user.downgrade();
+        user.data.resize(USER_SIZE, 0);
+        assert_eq!(user.version(), 1);
+        assert_eq!(user.creator(), message.key);
+
+        migrate(&mut [&mut user], AccountKind::User).unwrap();
+        assert_eq!(user.version(), LAYOUT_VERSION);
+        assert_eq!(user.creator(), message.key);
+        assert_eq!(
+            migrate(&mut [&mut user], AccountKind::User),
+            Err(MessageFeedError::AccountAlreadyMigrated.into())
+        );
+    }
+
+    #[test]
+    pub fn migrate_user_to_new_account() {
+        let mut user = TestAccount::user(1);
+        let mut message = TestAccount::message(2, "hello");
+        init_user(&mut user, &mut message).unwrap();
+        post(&mut user, &mut message, "hello", None).unwrap();
+        user.downgrade();
+
+        let mut new_user = TestAccount::user(3);
+        user.is_signer = false;
+        assert_eq!(
+            migrate(&mut [&mut user, &mut new_user], AccountKind::User),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+
+        user.is_signer = true;
+        migrate(&mut [&mut user, &mut new_user], AccountKind::User).unwrap();
+        assert_eq!(new_user.lamports, 2);
+        assert_eq!(new_user.creator(), message.key);
+        assert_eq!(user.lamports, 0);
+        assert!(user.data.iter().all(|&byte| byte == 0));
+
+        let mut reply = TestAccount::message(4, "hi");
+        post(&mut new_user, &mut reply, "hi", Some(&mut message)).unwrap();
+    }
+
+    #[test]
+    pub fn migrate_message_to_new_account() {
+        let mut creator = TestAccount::user(1);
+        let mut first_message = TestAccount::message(2, "hello");
+        init_user(&mut creator, &mut first_message).unwrap();
+        post(&mut creator, &mut first_message, "hello", None).unwrap();
+        let mut reply = TestAccount::message(3, "hi");
+        post(&mut creator, &mut reply, "hi", Some(&mut first_message)).unwrap();
+        first_message.downgrade();
+        reply.downgrade();
+
+        // A chain is named after its first message
+        let mut new_message = TestAccount::message(4, "hello");
+        assert_eq!(
+            migrate(
+                &mut [&mut first_message, &mut new_message],
+                AccountKind::Message
+            ),
+            Err(MessageFeedError::InvalidMigrationLinks.into())
+        );
+
+        let mut new_reply = TestAccount::message(5, "hey");
+        assert_eq!(
+            migrate(
+                &mut [&mut reply, &mut new_reply, &mut first_message],
+                AccountKind::Message
+            ),
+            Err(ProgramError::InvalidAccountData)
+        );
+        let mut new_reply = TestAccount::message(5, "hi");
+        assert_eq!(
+            migrate(
+                &mut [&mut reply, &mut new_reply, &mut creator],
+                AccountKind::Message
+            ),
+            Err(ProgramError::AccountDataTooSmall)
+        );
+
+        // Only the user who posted a message can move it
+        let mut other_user = TestAccount::user(7);
+        assert_eq!(
+            migrate(
+                &mut [
+                    &mut reply,
+                    &mut new_reply,
+                    &mut first_message,
+                    &mut other_user
+                ],
+                AccountKind::Message
+            ),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+        creator.is_signer = false;
+        assert_eq!(
+            migrate(
+                &mut [&mut reply, &mut new_reply, &mut first_message, &mut creator],
+                AccountKind::Message
+            ),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+        creator.is_signer = true;
+        migrate(
+            &mut [&mut reply, &mut new_reply, &mut first_message, &mut creator],
+            AccountKind::Message,
+        )
+        .unwrap();
+
+        let first_message_data = MessageAccountData::new(&mut first_message.data).unwrap();
+        assert_eq!(first_message_data.version, 1);
+        assert_eq!(first_message_data.next_message, &[5; 32]);
+        let reply_data = MessageAccountData::new(&mut new_reply.data).unwrap();
+        assert_eq!(reply_data.version, LAYOUT_VERSION);
+        assert_eq!(reply_data.text, b"hi");
+        assert_eq!(reply_data.from, &[1; 32]);
+        assert_eq!(reply_data.creator, &[2; 32]);
+
+        let mut next_reply = TestAccount::message(6, "hey");
+        post(&mut creator, &mut next_reply, "hey", Some(&mut new_reply)).unwrap();
+    }
+}
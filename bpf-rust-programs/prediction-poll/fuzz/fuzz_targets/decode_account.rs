@@ -0,0 +1,51 @@
+//! Decodes account data as every account type and reads back each field
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use prediction_poll_data::{
+    ClockData, CollectionData, Migration, PollData, PollOutcome, PollQuote, TallyData,
+};
+
+fuzz_target!(|data: &[u8]| {
+    let _ = ClockData::from_bytes(data);
+
+    let mut collection_data = data.to_vec();
+    if let Ok(collection) = CollectionData::from_bytes(&mut collection_data) {
+        assert!(collection.len() <= collection.capacity());
+        for poll in collection.to_vec() {
+            assert!(collection.contains(&poll));
+        }
+        let _ = collection.next_collection();
+    }
+
+    let mut tally_data = data.to_vec();
+    if let Ok(tally) = TallyData::from_bytes(&mut tally_data) {
+        assert!(tally.len() <= tally.capacity());
+        for (index, (key, wager)) in tally.iter().enumerate() {
+            assert_eq!(tally.key(index), key);
+            assert_eq!(tally.wager(index), wager);
+            let _ = tally.is_claimed(index);
+        }
+        let _ = tally.next_tally();
+    }
+
+    let mut poll_data = data.to_vec();
+    if let Ok(poll) = PollData::from_bytes(&mut poll_data) {
+        let quote = PollQuote::from(&poll);
+        for option in [PollOutcome::OptionA, PollOutcome::OptionB].iter() {
+            let _ = quote.odds_bps(*option);
+            let _ = quote.payout(*option, u64::MAX);
+            let _ = quote.projected_payout(*option, u64::MAX, u64::MAX);
+        }
+        let _ = quote.fee_adjusted_pot();
+    }
+
+    let mut migrated_data = data.to_vec();
+    if let Ok(migration) = Migration::new(&mut migrated_data) {
+        if migration.apply(&mut migrated_data).is_ok() {
+            let migrated = Migration::new(&mut migrated_data).unwrap();
+            assert!(!migrated.is_needed());
+            assert_eq!(migrated.migrated_len(), migration.migrated_len());
+        }
+    }
+});
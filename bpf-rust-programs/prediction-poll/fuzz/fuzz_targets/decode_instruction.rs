@@ -0,0 +1,24 @@
+//! Decodes instruction data and checks that re-encoding it round-trips
+#![no_main]
+
+use core::convert::TryFrom;
+use libfuzzer_sys::fuzz_target;
+use prediction_poll_data::{CommandData, Encode, InitPollData};
+
+fuzz_target!(|instruction_data: &[u8]| {
+    let (command, data) = match instruction_data.split_first() {
+        Some(split) => split,
+        None => return,
+    };
+
+    let _ = CommandData::try_from(*command);
+
+    // Flags decode from any nonzero byte, so the re-encoded bytes are compared
+    // against another round instead of the input
+    if let Ok(init) = InitPollData::from_bytes(data) {
+        let bytes = init.to_bytes();
+        assert_eq!(bytes.len(), init.encoded_len());
+        let decoded = InitPollData::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.to_bytes(), bytes);
+    }
+});
@@ -0,0 +1,145 @@
+//! Runs sequences of instructions against a fixed set of synthetic accounts
+//!
+//! Each step is encoded as `slot, account count, account indices, data length, data`.
+//! An account index selects from the accounts below, its high bit marks the account
+//! as a signer. Failed instructions are rolled back like the runtime would, and
+//! successful ones must not create or destroy lamports.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use prediction_poll::process_instruction;
+use prediction_poll_data::{MIN_COLLECTION_SIZE, MIN_TALLY_SIZE, PROTOCOL_TREASURY};
+use solana_sdk::{account_info::AccountInfo, pubkey::Pubkey, sysvar::clock};
+
+const SIGNER: u8 = 0x80;
+const POLL_SIZE: usize = 512;
+const TALLY_SIZE: usize = MIN_TALLY_SIZE + 41 * 3;
+
+struct Account {
+    key: Pubkey,
+    lamports: u64,
+    data: Vec<u8>,
+    owner: Pubkey,
+}
+
+impl Account {
+    fn new(seed: u8, owner: &Pubkey, lamports: u64, size: usize) -> Self {
+        Self {
+            key: Pubkey::new(&[seed; 32]),
+            lamports,
+            data: vec![0; size],
+            owner: *owner,
+        }
+    }
+
+    fn info(&mut self) -> AccountInfo<'_> {
+        AccountInfo::new(
+            &self.key,
+            false,
+            true,
+            &mut self.lamports,
+            &mut self.data,
+            &self.owner,
+            false,
+            0,
+        )
+    }
+}
+
+/// Clock, collection, poll, two tallies, creator, treasury, two funded wager
+/// accounts, a voter and a spare page for extending a tally or the collection
+fn accounts(program_id: &Pubkey) -> Vec<Account> {
+    let mut clock = Account::new(0, &Pubkey::default(), 1, 40);
+    clock.key = clock::id();
+    let mut treasury = Account::new(6, program_id, 0, 0);
+    treasury.key = Pubkey::new(&PROTOCOL_TREASURY);
+    vec![
+        clock,
+        Account::new(1, program_id, 0, MIN_COLLECTION_SIZE + 32 * 3),
+        Account::new(2, program_id, 1000, POLL_SIZE),
+        Account::new(3, program_id, 0, TALLY_SIZE),
+        Account::new(4, program_id, 0, TALLY_SIZE),
+        Account::new(5, program_id, 0, 0),
+        treasury,
+        Account::new(7, program_id, 1000, 0),
+        Account::new(8, program_id, 500, 0),
+        Account::new(9, &Pubkey::default(), 0, 0),
+        Account::new(10, program_id, 0, TALLY_SIZE),
+    ]
+}
+
+struct Step<'a> {
+    slot: u8,
+    accounts: &'a [u8],
+    data: &'a [u8],
+}
+
+fn next_step<'a>(input: &mut &'a [u8]) -> Option<Step<'a>> {
+    let (&slot, rest) = input.split_first()?;
+    let (&account_count, rest) = rest.split_first()?;
+    if rest.len() < account_count as usize {
+        return None;
+    }
+    let (accounts, rest) = rest.split_at(account_count as usize);
+    let (&data_len, rest) = rest.split_first()?;
+    if rest.len() < data_len as usize {
+        return None;
+    }
+    let (data, rest) = rest.split_at(data_len as usize);
+    *input = rest;
+    Some(Step {
+        slot,
+        accounts,
+        data,
+    })
+}
+
+fn total_lamports(infos: &[AccountInfo]) -> u128 {
+    infos.iter().map(|info| u128::from(info.lamports())).sum()
+}
+
+fuzz_target!(|input: &[u8]| {
+    let program_id = Pubkey::new(&[0xff; 32]);
+    let mut accounts = accounts(&program_id);
+    let infos: Vec<_> = accounts.iter_mut().map(Account::info).collect();
+    let total = total_lamports(&infos);
+
+    let mut input = input;
+    while let Some(step) = next_step(&mut input) {
+        let indices: Vec<_> = step
+            .accounts
+            .iter()
+            .map(|index| usize::from(index & !SIGNER) % infos.len())
+            .collect();
+        // The runtime shares one borrow between duplicate accounts, which the
+        // program does not expect, so those instructions are skipped
+        if (1..indices.len()).any(|i| indices[..i].contains(&indices[i])) {
+            continue;
+        }
+
+        infos[0].data.borrow_mut()[..8].copy_from_slice(&u64::from(step.slot).to_le_bytes());
+        let snapshot: Vec<_> = infos
+            .iter()
+            .map(|info| (info.lamports(), info.data.borrow().to_vec()))
+            .collect();
+
+        let instruction_accounts: Vec<_> = step
+            .accounts
+            .iter()
+            .zip(indices)
+            .map(|(index, i)| {
+                let mut info = infos[i].clone();
+                info.is_signer = index & SIGNER != 0;
+                info
+            })
+            .collect();
+        if process_instruction(&program_id, &instruction_accounts, step.data).is_err() {
+            for (info, (lamports, data)) in infos.iter().zip(snapshot) {
+                **info.lamports.borrow_mut() = lamports;
+                info.data.borrow_mut().copy_from_slice(&data);
+            }
+        }
+
+        assert_eq!(total_lamports(&infos), total);
+    }
+});
@@ -15,6 +15,8 @@ Supported actions:
     clean
     test
     clippy
+    miri
+    fuzz <target>
     fmt
 
 EOF
@@ -52,6 +54,16 @@ perform_action() {
         echo "clippy"
         cargo +nightly clippy
         ;;
+    miri)
+        echo "miri"
+        cargo +nightly miri test -p prediction-poll-data
+        ;;
+    fuzz)
+        echo "fuzz $2"
+        cd ..
+        mkdir -p fuzz/corpus/"$2"
+        cargo +nightly fuzz run "$2" fuzz/corpus/"$2" fuzz/seeds/"$2"
+        ;;
     fmt)
         echo "formatting"
         cargo fmt
@@ -70,4 +82,4 @@ perform_action() {
 
 set -e
 
-perform_action "$1"
+perform_action "$@"
@@ -7,7 +7,7 @@ mod program;
 mod result;
 mod util;
 
-use program::process_instruction;
+pub use program::process_instruction;
 use result::PollError;
 use solana_sdk::{
     account_info::AccountInfo, entrypoint, entrypoint::ProgramResult,
@@ -12,3 +12,22 @@ pub fn add_poll(collection: &mut CollectionData, poll_pubkey: &Pubkey) -> Progra
         Ok(())
     }
 }
+
+pub fn remove_poll(collection: &mut CollectionData, poll_pubkey: &Pubkey) -> ProgramResult {
+    if collection.remove_poll(poll_pubkey) {
+        Ok(())
+    } else {
+        Err(PollError::PollNotInCollection.into())
+    }
+}
+
+pub fn extend(collection: &mut CollectionData, next_pubkey: &Pubkey) -> ProgramResult {
+    if collection.next_collection().is_some() {
+        Err(PollError::CollectionAlreadyExtended.into())
+    } else if collection.len() < collection.capacity() {
+        Err(PollError::CollectionNotFull.into())
+    } else {
+        collection.set_next_collection(next_pubkey);
+        Ok(())
+    }
+}
@@ -1,16 +1,19 @@
 mod collection;
 mod poll;
 mod tally;
+#[cfg(test)]
+mod test;
 
 use crate::result::PollError;
 use crate::util::{
-    expect_data_type, expect_gt, expect_key, expect_min_size, expect_new_account, expect_owned_by,
-    expect_signed,
+    close_account, credit, debit, expect_data_type, expect_gt, expect_key, expect_lte,
+    expect_min_size, expect_new_account, expect_owned_by, expect_signed,
 };
 use core::convert::TryFrom;
 use prediction_poll_data::{
-    ClockData, CollectionData, CommandData, DataType, InitPollData, PollData, TallyData,
-    MIN_COLLECTION_SIZE, MIN_TALLY_SIZE,
+    relink, share, ClockData, CollectionData, CommandData, DataType, DisputeState, InitPollData,
+    Migration, PollData, PollOutcome, PollQuote, TallyData, MAX_FEE_BPS, MIN_COLLECTION_SIZE,
+    MIN_TALLY_SIZE, PROTOCOL_FEE_BPS, PROTOCOL_TREASURY,
 };
 use solana_sdk::{
     account_info::AccountInfo, entrypoint::ProgramResult, info, program_utils::next_account_info,
@@ -22,14 +25,25 @@ pub fn process_instruction(
     accounts: &[AccountInfo],
     instruction_data: &[u8],
 ) -> ProgramResult {
-    let (command, data) = instruction_data.split_at(1);
-    let command =
-        CommandData::try_from(command[0].to_le()).map_err(|_| PollError::InvalidCommand)?;
+    let (command, data) = instruction_data
+        .split_first()
+        .ok_or(PollError::InvalidInput)?;
+    let command = CommandData::try_from(command.to_le()).map_err(|_| PollError::InvalidCommand)?;
     match command {
         CommandData::InitCollection => init_collection(program_id, accounts),
         CommandData::InitPoll => init_poll(program_id, accounts, data),
         CommandData::SubmitVote => submit_vote(program_id, accounts),
         CommandData::SubmitClaim => submit_claim(program_id, accounts),
+        CommandData::ClaimPayout => claim_payout(program_id, accounts),
+        CommandData::ExtendCollection => extend_collection(program_id, accounts),
+        CommandData::ClosePoll => close_poll(program_id, accounts),
+        CommandData::ResolvePoll => resolve_poll(program_id, accounts, data),
+        CommandData::ChallengeOutcome => challenge_outcome(program_id, accounts),
+        CommandData::SettleDispute => settle_dispute(program_id, accounts, data),
+        CommandData::CancelPoll => cancel_poll(program_id, accounts),
+        CommandData::AddTallyPage => add_tally_page(program_id, accounts),
+        CommandData::Migrate => migrate(program_id, accounts),
+        CommandData::RefundWagers => refund_wagers(program_id, accounts),
     }
 }
 
@@ -43,7 +57,33 @@ fn init_collection(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResu
     expect_min_size(&collection_account.data.borrow(), MIN_COLLECTION_SIZE)?;
     expect_new_account(collection_account)?;
 
-    collection_account.data.borrow_mut()[0] = DataType::Collection as u8;
+    CollectionData::copy_to_bytes(&mut collection_account.data.borrow_mut())
+        .map_err(PollError::from)?;
+
+    Ok(())
+}
+
+fn extend_collection(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    info!("extend collection");
+    let account_info_iter = &mut accounts.iter();
+
+    let next_collection_account = next_account_info(account_info_iter)?;
+    expect_signed(next_collection_account)?;
+    expect_owned_by(next_collection_account, program_id)?;
+    expect_min_size(&next_collection_account.data.borrow(), MIN_COLLECTION_SIZE)?;
+    expect_new_account(next_collection_account)?;
+
+    let collection_account = next_account_info(account_info_iter)?;
+    expect_owned_by(collection_account, program_id)?;
+    expect_data_type(collection_account, DataType::Collection)?;
+
+    let mut collection_account_data = collection_account.data.borrow_mut();
+    let mut collection =
+        CollectionData::from_bytes(&mut collection_account_data).map_err(PollError::from)?;
+    collection::extend(&mut collection, next_collection_account.key)?;
+
+    CollectionData::copy_to_bytes(&mut next_collection_account.data.borrow_mut())
+        .map_err(PollError::from)?;
 
     Ok(())
 }
@@ -80,12 +120,30 @@ fn init_poll(program_id: &Pubkey, accounts: &[AccountInfo], init_data: &[u8]) ->
     expect_key(clock_account, &clock::id())?;
 
     let mut collection_account_data = collection_account.data.borrow_mut();
-    let mut collection = CollectionData::from_bytes(&mut collection_account_data);
-    let clock = ClockData::from_bytes(&clock_account.data.borrow());
-    let init_poll = InitPollData::from_bytes(init_data);
+    let mut collection =
+        CollectionData::from_bytes(&mut collection_account_data).map_err(PollError::from)?;
+    let clock = ClockData::from_bytes(&clock_account.data.borrow()).map_err(PollError::from)?;
+    let init_poll = InitPollData::from_bytes(init_data).map_err(PollError::from)?;
     expect_gt(init_poll.header_len, 0)?;
     expect_gt(init_poll.option_a_len, 0)?;
     expect_gt(init_poll.option_b_len, 0)?;
+    if init_poll.max_wager > 0 {
+        expect_lte(init_poll.min_wager, init_poll.max_wager)?;
+    }
+    // Polls that end at a timestamp must be able to take votes
+    if init_poll.end_time != 0 {
+        expect_gt(init_poll.end_time, clock.unix_timestamp)?;
+    }
+    // Submitted outcomes must be open to challenges that someone can settle
+    if init_poll.resolver_key != Pubkey::default()
+        && (init_poll.authority_key == Pubkey::default() || init_poll.dispute_window == 0)
+    {
+        return Err(PollError::MissingDisputeAuthority.into());
+    }
+    expect_lte(
+        u32::from(init_poll.creator_fee_bps) + u32::from(PROTOCOL_FEE_BPS),
+        u32::from(MAX_FEE_BPS),
+    )?;
 
     collection::add_poll(&mut collection, poll_account.key)?;
     let mut poll_account_data = poll_account.data.borrow_mut();
@@ -96,10 +154,21 @@ fn init_poll(program_id: &Pubkey, accounts: &[AccountInfo], init_data: &[u8]) ->
         tally_a_account.key,
         tally_b_account.key,
         clock.slot,
-    );
-
-    tally_a_account.data.borrow_mut()[0] = DataType::Tally as u8;
-    tally_b_account.data.borrow_mut()[0] = DataType::Tally as u8;
+    )
+    .map_err(PollError::from)?;
+
+    TallyData::copy_to_bytes(
+        &mut tally_a_account.data.borrow_mut(),
+        poll_account.key,
+        PollOutcome::OptionA,
+    )
+    .map_err(PollError::from)?;
+    TallyData::copy_to_bytes(
+        &mut tally_b_account.data.borrow_mut(),
+        poll_account.key,
+        PollOutcome::OptionB,
+    )
+    .map_err(PollError::from)?;
 
     Ok(())
 }
@@ -128,18 +197,47 @@ fn submit_vote(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
 
     let clock_data = clock_account.data.borrow_mut();
     info!(0, 0, 0, 0, line!());
-    let clock = ClockData::from_bytes(&clock_data);
+    let clock = ClockData::from_bytes(&clock_data).map_err(PollError::from)?;
     info!(0, 0, 0, 0, line!());
     let mut poll_data = poll_account.data.borrow_mut();
     info!(0, 0, 0, 0, line!());
-    let mut poll = PollData::from_bytes(&mut poll_data);
+    let mut poll = PollData::from_bytes(&mut poll_data).map_err(PollError::from)?;
     info!(0, 0, 0, 0, line!());
-    let mut tally_data = tally_account.data.borrow_mut();
-    let mut tally = TallyData::from_bytes(&mut tally_data);
+
+    // Overflow pages of the selected option follow the clock
+    let pages = tally::next_pages(
+        tally_account,
+        account_info_iter,
+        poll::page_count(&poll, tally_account.key)?,
+    )?;
+    let mut pages_data: Vec<_> = pages.iter().map(|page| page.data.borrow_mut()).collect();
+    let mut tallies = tally::load_pages(&pages, &mut pages_data)?;
 
     info!(0, 0, 0, 0, line!());
-    if poll.last_block < clock.slot {
-        return Err(PollError::PollAlreadyFinished.into());
+    poll::expect_open(&poll, &clock)?;
+    poll::expect_not_cancelled(&poll)?;
+
+    // Every page of the opposing option is only passed when voters may not hedge
+    // or their total wager across both options is capped
+    let other_pages = if poll.no_hedging || poll.voter_cap > 0 {
+        let other_tally_account = next_account_info(account_info_iter)?;
+        let other_tally_key = poll::other_tally_key(&poll, tally_account.key)?;
+        expect_key(other_tally_account, &other_tally_key)?;
+        tally::next_pages(
+            other_tally_account,
+            account_info_iter,
+            poll::page_count(&poll, &other_tally_key)?,
+        )?
+    } else {
+        vec![]
+    };
+    let mut other_pages_data: Vec<_> = other_pages
+        .iter()
+        .map(|page| page.data.borrow_mut())
+        .collect();
+    let other_tallies = tally::load_pages(&other_pages, &mut other_pages_data)?;
+    if poll.no_hedging {
+        tally::expect_no_wager(&other_tallies, payout_account.key)?;
     }
 
     if user_account.lamports() == 0 {
@@ -148,11 +246,19 @@ fn submit_vote(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
     info!(0, 0, 0, 0, line!());
 
     let wager = user_account.lamports();
+    poll::expect_wager_in_bounds(&poll, wager)?;
+    tally::expect_under_cap(
+        &tallies,
+        &other_tallies,
+        payout_account.key,
+        wager,
+        poll.voter_cap,
+    )?;
     poll::record_wager(&mut poll, tally_account.key, wager)?;
-    tally::record_wager(&mut tally, payout_account.key, wager)?;
+    tally::record_wager(&mut tallies, payout_account.key, wager)?;
     info!(0, 0, 0, 0, line!());
 
-    **poll_account.lamports.borrow_mut() += wager;
+    credit(poll_account, wager)?;
     **user_account.lamports.borrow_mut() = 0;
     info!(0, 0, 0, 0, line!());
 
@@ -175,26 +281,498 @@ fn submit_claim(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult
     let clock_account = next_account_info(account_info_iter)?;
     expect_key(clock_account, &clock::id())?;
 
-    if poll_account.lamports() <= 1 {
+    let creator_account = next_account_info(account_info_iter)?;
+    let treasury_account = next_account_info(account_info_iter)?;
+
+    let clock_data = clock_account.data.borrow_mut();
+    let clock = ClockData::from_bytes(&clock_data).map_err(PollError::from)?;
+    let mut poll_data = poll_account.data.borrow_mut();
+    let poll = PollData::from_bytes(&mut poll_data).map_err(PollError::from)?;
+    let mut tally_data = tally_account.data.borrow_mut();
+    let mut tally = TallyData::from_bytes(&mut tally_data).map_err(PollError::from)?;
+
+    poll::expect_finished(&poll, &clock)?;
+    poll::expect_finalized(&poll, &clock)?;
+
+    expect_key(creator_account, &poll.creator_key)?;
+    expect_key(treasury_account, &Pubkey::new(&PROTOCOL_TREASURY))?;
+
+    // Each tally page is paid its share of the recorded wagers, so pages can be
+    // settled in separate transactions. Wagers whose voter claimed their own
+    // payout already took their share out of the pot.
+    let option = tally::expect_option(&tally, poll_account.key)?;
+    let winning_quantity = poll::check_winning_tally(&poll, option)?;
+    let unclaimed_quantity = tally::unclaimed_quantity(&tally)?;
+    let quote = PollQuote::from(&poll);
+    let page_pot =
+        share(quote.pot(), unclaimed_quantity, winning_quantity).ok_or(PollError::Overflow)?;
+    if poll_account.lamports() <= page_pot {
         return Err(PollError::PollHasNoFunds.into());
     }
+    debit(poll_account, page_pot)?;
+
+    let (creator_fee, protocol_fee) = quote.fees(page_pot);
+    tally::payout(
+        &mut tally,
+        account_info_iter.as_slice(),
+        unclaimed_quantity,
+        quote.after_fees(page_pot),
+    )?;
+
+    credit(creator_account, creator_fee)?;
+    credit(treasury_account, protocol_fee)?;
+
+    Ok(())
+}
+
+fn claim_payout(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    info!("claim payout");
+    let account_info_iter = &mut accounts.iter();
+
+    let payout_account = next_account_info(account_info_iter)?;
+    expect_signed(payout_account)?;
+
+    let poll_account = next_account_info(account_info_iter)?;
+    expect_owned_by(poll_account, program_id)?;
+    expect_data_type(poll_account, DataType::Poll)?;
+
+    let tally_account = next_account_info(account_info_iter)?;
+    expect_owned_by(tally_account, program_id)?;
+    expect_data_type(tally_account, DataType::Tally)?;
+
+    let clock_account = next_account_info(account_info_iter)?;
+    expect_key(clock_account, &clock::id())?;
+
+    let creator_account = next_account_info(account_info_iter)?;
+    let treasury_account = next_account_info(account_info_iter)?;
 
     let clock_data = clock_account.data.borrow_mut();
-    let clock = ClockData::from_bytes(&clock_data);
+    let clock = ClockData::from_bytes(&clock_data).map_err(PollError::from)?;
     let mut poll_data = poll_account.data.borrow_mut();
-    let poll = PollData::from_bytes(&mut poll_data);
+    let poll = PollData::from_bytes(&mut poll_data).map_err(PollError::from)?;
     let mut tally_data = tally_account.data.borrow_mut();
-    let tally = TallyData::from_bytes(&mut tally_data);
+    let mut tally = TallyData::from_bytes(&mut tally_data).map_err(PollError::from)?;
+
+    poll::expect_finished(&poll, &clock)?;
+    poll::expect_finalized(&poll, &clock)?;
+
+    expect_key(creator_account, &poll.creator_key)?;
+    expect_key(treasury_account, &Pubkey::new(&PROTOCOL_TREASURY))?;
+
+    // Shares are computed from the recorded wagers rather than the poll balance
+    // so that every voter receives the same amount regardless of claim order
+    let quote = PollQuote::from(&poll);
+    let option = tally::expect_option(&tally, poll_account.key)?;
+    let winning_quantity = poll::check_winning_tally(&poll, option)?;
+    let portion = tally::claim(
+        &mut tally,
+        payout_account.key,
+        winning_quantity,
+        quote.pot(),
+    )?;
+
+    if poll_account.lamports() <= portion {
+        return Err(PollError::PollHasNoFunds.into());
+    }
+
+    // Each claimant pays their proportional part of the fees
+    let (creator_fee, protocol_fee) = quote.fees(portion);
+    debit(poll_account, portion)?;
+    credit(payout_account, quote.after_fees(portion))?;
+    credit(creator_account, creator_fee)?;
+    credit(treasury_account, protocol_fee)?;
+
+    Ok(())
+}
 
-    if poll.last_block > clock.slot {
-        return Err(PollError::PollNotFinished.into());
+fn close_poll(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    info!("close poll");
+    let account_info_iter = &mut accounts.iter();
+
+    let creator_account = next_account_info(account_info_iter)?;
+    expect_signed(creator_account)?;
+
+    let poll_account = next_account_info(account_info_iter)?;
+    expect_owned_by(poll_account, program_id)?;
+    expect_data_type(poll_account, DataType::Poll)?;
+
+    let collection_account = next_account_info(account_info_iter)?;
+    expect_owned_by(collection_account, program_id)?;
+    expect_data_type(collection_account, DataType::Collection)?;
+
+    let tally_a_account = next_account_info(account_info_iter)?;
+    expect_owned_by(tally_a_account, program_id)?;
+    expect_data_type(tally_a_account, DataType::Tally)?;
+
+    let tally_b_account = next_account_info(account_info_iter)?;
+    expect_owned_by(tally_b_account, program_id)?;
+    expect_data_type(tally_b_account, DataType::Tally)?;
+
+    let clock_account = next_account_info(account_info_iter)?;
+    expect_key(clock_account, &clock::id())?;
+
+    // Overflow pages of option A followed by option B are closed as well
+    let (pages_a, pages_b) = {
+        let clock_data = clock_account.data.borrow();
+        let clock = ClockData::from_bytes(&clock_data).map_err(PollError::from)?;
+        let mut poll_data = poll_account.data.borrow_mut();
+        let poll = PollData::from_bytes(&mut poll_data).map_err(PollError::from)?;
+
+        expect_key(creator_account, &poll.creator_key)?;
+        expect_key(tally_a_account, &poll.option_a.tally_key)?;
+        expect_key(tally_b_account, &poll.option_b.tally_key)?;
+
+        let pages_a = tally::next_pages(
+            tally_a_account,
+            account_info_iter,
+            poll.option_a.page_count.get(),
+        )?;
+        let pages_b = tally::next_pages(
+            tally_b_account,
+            account_info_iter,
+            poll.option_b.page_count.get(),
+        )?;
+        let mut pages_a_data: Vec<_> = pages_a.iter().map(|page| page.data.borrow_mut()).collect();
+        let tallies_a = tally::load_pages(&pages_a, &mut pages_a_data)?;
+        let mut pages_b_data: Vec<_> = pages_b.iter().map(|page| page.data.borrow_mut()).collect();
+        let tallies_b = tally::load_pages(&pages_b, &mut pages_b_data)?;
+
+        // Polls are settled once every winning wager has been paid out, or
+        // every wager has been refunded when the poll was cancelled or nobody
+        // can claim because nothing was wagered on the winning option
+        if !poll.is_cancelled() {
+            poll::expect_finished(&poll, &clock)?;
+            poll::expect_finalized(&poll, &clock)?;
+        }
+        let settled_tallies = if !poll.is_cancelled() && poll::has_winning_wagers(&poll)? {
+            if poll::winning_tally_key(&poll)? == Some(*tally_a_account.key) {
+                tallies_a
+            } else {
+                tallies_b
+            }
+        } else {
+            tallies_a.into_iter().chain(tallies_b).collect()
+        };
+        for tally in &settled_tallies {
+            tally::expect_all_claimed(tally)?;
+        }
+
+        let mut collection_account_data = collection_account.data.borrow_mut();
+        let mut collection =
+            CollectionData::from_bytes(&mut collection_account_data).map_err(PollError::from)?;
+        collection::remove_poll(&mut collection, poll_account.key)?;
+        (pages_a, pages_b)
+    };
+
+    close_account(poll_account, creator_account)?;
+    for page in pages_a.iter().chain(pages_b.iter()) {
+        close_account(page, creator_account)?;
     }
 
-    let pot = **poll_account.lamports.borrow_mut() - 1;
-    **poll_account.lamports.borrow_mut() = 1;
+    Ok(())
+}
+
+fn resolve_poll(program_id: &Pubkey, accounts: &[AccountInfo], outcome: &[u8]) -> ProgramResult {
+    info!("resolve poll");
+    let account_info_iter = &mut accounts.iter();
 
-    let winning_quantity = poll::check_winning_tally(&poll, tally_account.key)?;
-    tally::payout(&tally, account_info_iter.as_slice(), winning_quantity, pot)?;
+    let resolver_account = next_account_info(account_info_iter)?;
+    expect_signed(resolver_account)?;
+
+    let poll_account = next_account_info(account_info_iter)?;
+    expect_owned_by(poll_account, program_id)?;
+    expect_data_type(poll_account, DataType::Poll)?;
+
+    let clock_account = next_account_info(account_info_iter)?;
+    expect_key(clock_account, &clock::id())?;
+
+    if outcome.len() != 1 {
+        return Err(PollError::InvalidInput.into());
+    }
+
+    let clock_data = clock_account.data.borrow();
+    let clock = ClockData::from_bytes(&clock_data).map_err(PollError::from)?;
+    let mut poll_data = poll_account.data.borrow_mut();
+    let mut poll = PollData::from_bytes(&mut poll_data).map_err(PollError::from)?;
+
+    if !poll.has_resolver() {
+        return Err(PollError::PollHasNoResolver.into());
+    }
+    expect_key(resolver_account, &poll.resolver_key)?;
+    poll::expect_finished(&poll, &clock)?;
+
+    poll::resolve(&mut poll, &clock, PollOutcome::from(outcome[0]))
+}
+
+fn challenge_outcome(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    info!("challenge outcome");
+    let account_info_iter = &mut accounts.iter();
+
+    let user_account = next_account_info(account_info_iter)?;
+    expect_signed(user_account)?;
+    expect_owned_by(user_account, program_id)?;
+
+    let poll_account = next_account_info(account_info_iter)?;
+    expect_owned_by(poll_account, program_id)?;
+    expect_data_type(poll_account, DataType::Poll)?;
+
+    let refund_account = next_account_info(account_info_iter)?;
+    let clock_account = next_account_info(account_info_iter)?;
+    expect_key(clock_account, &clock::id())?;
+
+    let clock_data = clock_account.data.borrow();
+    let clock = ClockData::from_bytes(&clock_data).map_err(PollError::from)?;
+    let mut poll_data = poll_account.data.borrow_mut();
+    let mut poll = PollData::from_bytes(&mut poll_data).map_err(PollError::from)?;
+
+    let stake = user_account.lamports();
+    poll::challenge(&mut poll, &clock, refund_account.key, stake)?;
+
+    credit(poll_account, stake)?;
+    **user_account.lamports.borrow_mut() = 0;
+
+    Ok(())
+}
+
+fn settle_dispute(program_id: &Pubkey, accounts: &[AccountInfo], outcome: &[u8]) -> ProgramResult {
+    info!("settle dispute");
+    let account_info_iter = &mut accounts.iter();
+
+    let authority_account = next_account_info(account_info_iter)?;
+    expect_signed(authority_account)?;
+
+    let poll_account = next_account_info(account_info_iter)?;
+    expect_owned_by(poll_account, program_id)?;
+    expect_data_type(poll_account, DataType::Poll)?;
+
+    let refund_account = next_account_info(account_info_iter)?;
+
+    if outcome.len() != 1 {
+        return Err(PollError::InvalidInput.into());
+    }
+
+    let mut poll_data = poll_account.data.borrow_mut();
+    let mut poll = PollData::from_bytes(&mut poll_data).map_err(PollError::from)?;
+
+    expect_key(authority_account, &poll.dispute.authority_key)?;
+    expect_key(
+        refund_account,
+        &Pubkey::new(&poll.dispute.challenger_key[..]),
+    )?;
+
+    // An upheld outcome forfeits the stake to the winners' pot
+    let stake = poll.dispute.stake.get();
+    if poll::settle_dispute(&mut poll, PollOutcome::from(outcome[0]))? {
+        debit(poll_account, stake)?;
+        credit(refund_account, stake)?;
+    }
+
+    Ok(())
+}
+
+fn cancel_poll(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    info!("cancel poll");
+    let account_info_iter = &mut accounts.iter();
+
+    let creator_account = next_account_info(account_info_iter)?;
+
+    let poll_account = next_account_info(account_info_iter)?;
+    expect_owned_by(poll_account, program_id)?;
+    expect_data_type(poll_account, DataType::Poll)?;
+
+    let clock_account = next_account_info(account_info_iter)?;
+    expect_key(clock_account, &clock::id())?;
+
+    let clock_data = clock_account.data.borrow();
+    let clock = ClockData::from_bytes(&clock_data).map_err(PollError::from)?;
+    let mut poll_data = poll_account.data.borrow_mut();
+    let mut poll = PollData::from_bytes(&mut poll_data).map_err(PollError::from)?;
+
+    // Polls stalled waiting on the resolver or dispute authority can be
+    // cancelled by anyone, other polls only by the creator before they end
+    if !poll::is_stalled(&poll, &clock) {
+        expect_signed(creator_account)?;
+        expect_key(creator_account, &poll.creator_key)?;
+        poll::expect_not_finished(&poll, &clock)?;
+    }
+
+    // Wagers are refunded one tally page at a time afterwards
+    poll::cancel(&mut poll)?;
+
+    // The stake of an unsettled challenge is refunded to the challenger
+    if DisputeState::from(*poll.dispute.state) == DisputeState::Challenged {
+        let challenger_account = next_account_info(account_info_iter)?;
+        expect_key(
+            challenger_account,
+            &Pubkey::new(&poll.dispute.challenger_key[..]),
+        )?;
+        let stake = poll.dispute.stake.get();
+        poll.dispute.settle(true);
+        debit(poll_account, stake)?;
+        credit(challenger_account, stake)?;
+    }
+
+    Ok(())
+}
+
+fn refund_wagers(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    info!("refund wagers");
+    // No signer needed
+    let account_info_iter = &mut accounts.iter();
+
+    let poll_account = next_account_info(account_info_iter)?;
+    expect_owned_by(poll_account, program_id)?;
+    expect_data_type(poll_account, DataType::Poll)?;
+
+    let tally_account = next_account_info(account_info_iter)?;
+    expect_owned_by(tally_account, program_id)?;
+    expect_data_type(tally_account, DataType::Tally)?;
+
+    let clock_account = next_account_info(account_info_iter)?;
+    expect_key(clock_account, &clock::id())?;
+
+    let clock_data = clock_account.data.borrow();
+    let clock = ClockData::from_bytes(&clock_data).map_err(PollError::from)?;
+    let mut poll_data = poll_account.data.borrow_mut();
+    let poll = PollData::from_bytes(&mut poll_data).map_err(PollError::from)?;
+    let mut tally_data = tally_account.data.borrow_mut();
+    let mut tally = TallyData::from_bytes(&mut tally_data).map_err(PollError::from)?;
+
+    poll::expect_refundable(&poll, &clock)?;
+
+    // Any page of either option can be refunded, each in its own transaction
+    tally::expect_option(&tally, poll_account.key)?;
+    let refunded = tally::refund(&mut tally, account_info_iter.as_slice())?;
+    debit(poll_account, refunded)?;
 
     Ok(())
 }
+
+fn add_tally_page(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    info!("add tally page");
+    let account_info_iter = &mut accounts.iter();
+
+    let next_tally_account = next_account_info(account_info_iter)?;
+    expect_signed(next_tally_account)?;
+    expect_owned_by(next_tally_account, program_id)?;
+    expect_min_size(&next_tally_account.data.borrow(), MIN_TALLY_SIZE)?;
+    expect_new_account(next_tally_account)?;
+
+    let poll_account = next_account_info(account_info_iter)?;
+    expect_owned_by(poll_account, program_id)?;
+    expect_data_type(poll_account, DataType::Poll)?;
+
+    let tally_account = next_account_info(account_info_iter)?;
+    expect_owned_by(tally_account, program_id)?;
+    expect_data_type(tally_account, DataType::Tally)?;
+
+    let mut poll_data = poll_account.data.borrow_mut();
+    let mut poll = PollData::from_bytes(&mut poll_data).map_err(PollError::from)?;
+    let mut tally_data = tally_account.data.borrow_mut();
+    let mut tally = TallyData::from_bytes(&mut tally_data).map_err(PollError::from)?;
+
+    poll::expect_not_cancelled(&poll)?;
+    tally::add_page(&mut tally, poll_account.key, next_tally_account.key)?;
+    poll::add_page(&mut poll, tally.option)?;
+
+    TallyData::copy_to_bytes(
+        &mut next_tally_account.data.borrow_mut(),
+        poll_account.key,
+        tally.option,
+    )
+    .map_err(PollError::from)?;
+
+    Ok(())
+}
+
+fn migrate(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    info!("migrate");
+    let account_info_iter = &mut accounts.iter();
+
+    let account = next_account_info(account_info_iter)?;
+    expect_owned_by(account, program_id)?;
+
+    let mut migration = Migration::new(&mut account.data.borrow_mut()).map_err(PollError::from)?;
+    if !migration.is_needed() {
+        return Err(PollError::AccountAlreadyMigrated.into());
+    }
+
+    // Legacy tallies don't name their poll, it is passed after the tally and
+    // has to be migrated first
+    let poll_account = if migration.needs_poll() {
+        let poll_account = next_account_info(account_info_iter)?;
+        expect_owned_by(poll_account, program_id)?;
+        expect_data_type(poll_account, DataType::Poll)?;
+        let mut poll_data = poll_account.data.borrow_mut();
+        let poll = PollData::from_bytes(&mut poll_data).map_err(PollError::from)?;
+        let option = poll::tally_option(&poll, account.key)?;
+        migration
+            .set_poll(poll_account.key, option)
+            .map_err(PollError::from)?;
+        Some(poll_account)
+    } else {
+        None
+    };
+
+    if migration.migrated_len() <= account.data.borrow().len() {
+        migration
+            .apply(&mut account.data.borrow_mut())
+            .map_err(PollError::from)?;
+        return Ok(());
+    }
+
+    // Legacy collections are never linked to and can't move, the polls that no
+    // longer fit are added to a new page linked from the migrated collection
+    if migration.to.data_type as u8 == DataType::Collection as u8 {
+        let next_account = next_account_info(account_info_iter)?;
+        expect_signed(next_account)?;
+        expect_owned_by(next_account, program_id)?;
+        expect_new_account(next_account)?;
+        let next_data = migration
+            .split_collection(account.data.borrow().len(), next_account.key)
+            .map_err(PollError::from)?;
+        expect_min_size(&next_account.data.borrow(), next_data.len())?;
+        next_account.data.borrow_mut()[..next_data.len()].copy_from_slice(&next_data);
+        migration
+            .apply(&mut account.data.borrow_mut())
+            .map_err(PollError::from)?;
+        return Ok(());
+    }
+
+    // Legacy polls keep a single lamport once paid out, the wagers were
+    // claimed and moving would make the new account's rent claimable again
+    if migration.to.data_type as u8 == DataType::Poll as u8 && account.lamports() <= 1 {
+        return Err(PollError::PollHasNoFunds.into());
+    }
+
+    // Accounts without room for the new layout move to a new account, and every
+    // account linking to the old key is passed after it so the links follow
+    let new_account = next_account_info(account_info_iter)?;
+    expect_signed(new_account)?;
+    expect_owned_by(new_account, program_id)?;
+    expect_min_size(&new_account.data.borrow(), migration.migrated_len())?;
+    expect_new_account(new_account)?;
+
+    migration
+        .apply_to(&mut new_account.data.borrow_mut())
+        .map_err(PollError::from)?;
+
+    let mut links = 0;
+    for linked_account in poll_account.into_iter().chain(account_info_iter) {
+        expect_owned_by(linked_account, program_id)?;
+        links += relink(
+            &mut linked_account.data.borrow_mut(),
+            account.key,
+            new_account.key,
+        )
+        .map_err(PollError::from)?;
+    }
+
+    // Legacy polls are only linked from their collection and legacy tallies
+    // from their poll
+    if links != 1 {
+        return Err(PollError::InvalidMigrationLinks.into());
+    }
+
+    close_account(account, new_account)
+}
@@ -1,8 +1,108 @@
 use crate::result::PollError;
-use prediction_poll_data::PollData;
+use prediction_poll_data::{ClockData, DisputeState, PollData, PollOutcome, MIN_DISPUTE_STAKE};
 use solana_sdk::{entrypoint::ProgramResult, program_error::ProgramError, pubkey::Pubkey};
 
+pub fn expect_not_cancelled(poll: &PollData) -> ProgramResult {
+    if poll.is_cancelled() {
+        return Err(PollError::PollCancelled.into());
+    }
+    Ok(())
+}
+
+/// Votes are accepted from the first block until the poll deadline
+pub fn expect_open(poll: &PollData, clock: &ClockData) -> ProgramResult {
+    if poll.first_block > clock.slot {
+        return Err(PollError::PollNotStarted.into());
+    }
+    expect_not_finished(poll, clock)
+}
+
+pub fn expect_not_finished(poll: &PollData, clock: &ClockData) -> ProgramResult {
+    let finished = if poll.has_end_time() {
+        poll.end_time < clock.unix_timestamp
+    } else {
+        poll.last_block < clock.slot
+    };
+    if finished {
+        return Err(PollError::PollAlreadyFinished.into());
+    }
+    Ok(())
+}
+
+pub fn expect_finished(poll: &PollData, clock: &ClockData) -> ProgramResult {
+    let finished = if poll.has_end_time() {
+        poll.end_time <= clock.unix_timestamp
+    } else {
+        poll.last_block <= clock.slot
+    };
+    if !finished {
+        return Err(PollError::PollNotFinished.into());
+    }
+    Ok(())
+}
+
+pub fn cancel(poll: &mut PollData) -> ProgramResult {
+    expect_not_cancelled(poll)?;
+    *poll.outcome = PollOutcome::Cancelled as u8;
+    Ok(())
+}
+
+/// Limits of zero are not enforced
+pub fn expect_wager_in_bounds(poll: &PollData, wager: u64) -> ProgramResult {
+    if wager < poll.min_wager {
+        return Err(PollError::WagerBelowMinimum.into());
+    }
+    if poll.max_wager > 0 && wager > poll.max_wager {
+        return Err(PollError::WagerAboveMaximum.into());
+    }
+    Ok(())
+}
+
+/// Key of the tally for the option that was not selected
+pub fn other_tally_key(poll: &PollData, tally_pubkey: &Pubkey) -> Result<Pubkey, ProgramError> {
+    if poll.option_a.tally_key == *tally_pubkey {
+        Ok(poll.option_b.tally_key)
+    } else if poll.option_b.tally_key == *tally_pubkey {
+        Ok(poll.option_a.tally_key)
+    } else {
+        Err(PollError::InvalidTallyKey.into())
+    }
+}
+
+/// Option recorded by the given head tally
+pub fn tally_option(poll: &PollData, tally_pubkey: &Pubkey) -> Result<PollOutcome, ProgramError> {
+    if poll.option_a.tally_key == *tally_pubkey {
+        Ok(PollOutcome::OptionA)
+    } else if poll.option_b.tally_key == *tally_pubkey {
+        Ok(PollOutcome::OptionB)
+    } else {
+        Err(PollError::InvalidTallyKey.into())
+    }
+}
+
+/// Number of tally pages for the option with the given head tally
+pub fn page_count(poll: &PollData, tally_pubkey: &Pubkey) -> Result<u32, ProgramError> {
+    if poll.option_a.tally_key == *tally_pubkey {
+        Ok(poll.option_a.page_count.get())
+    } else if poll.option_b.tally_key == *tally_pubkey {
+        Ok(poll.option_b.page_count.get())
+    } else {
+        Err(PollError::InvalidTallyKey.into())
+    }
+}
+
+pub fn add_page(poll: &mut PollData, option: PollOutcome) -> ProgramResult {
+    let page_count = match option {
+        PollOutcome::OptionA => &mut poll.option_a.page_count,
+        PollOutcome::OptionB => &mut poll.option_b.page_count,
+        _ => return Err(PollError::InvalidTallyKey.into()),
+    };
+    page_count.set(page_count.get().checked_add(1).ok_or(PollError::Overflow)?);
+    Ok(())
+}
+
 pub fn record_wager(poll: &mut PollData, tally_pubkey: &Pubkey, wager: u64) -> ProgramResult {
+    let has_resolver = poll.has_resolver();
     let (selected, unselected) = if poll.option_a.tally_key == *tally_pubkey {
         (&mut poll.option_a, &mut poll.option_b)
     } else if poll.option_b.tally_key == *tally_pubkey {
@@ -11,28 +111,199 @@ pub fn record_wager(poll: &mut PollData, tally_pubkey: &Pubkey, wager: u64) -> P
         return Err(PollError::InvalidTallyKey.into());
     };
 
-    if *selected.quantity + wager == *unselected.quantity {
+    // The pot must also fit so payouts can be computed from it
+    let quantity = selected
+        .quantity
+        .get()
+        .checked_add(wager)
+        .ok_or(PollError::Overflow)?;
+    quantity
+        .checked_add(unselected.quantity.get())
+        .ok_or(PollError::Overflow)?;
+
+    // Ties only matter when the most wagered option wins
+    if !has_resolver && quantity == unselected.quantity.get() {
         return Err(PollError::PollCannotBeEven.into());
     }
 
-    *selected.quantity += wager;
+    selected.quantity.set(quantity);
     Ok(())
 }
 
-pub fn check_winning_tally(poll: &PollData, tally_pubkey: &Pubkey) -> Result<u64, ProgramError> {
-    if poll.option_a.tally_key == *tally_pubkey {
-        if *poll.option_a.quantity > *poll.option_b.quantity {
-            Ok(*poll.option_a.quantity)
-        } else {
-            Err(PollError::CannotPayoutToLosers.into())
+/// Checks that a tally page belongs to the winning option and returns its quantity
+pub fn check_winning_tally(poll: &PollData, option: PollOutcome) -> Result<u64, ProgramError> {
+    let (quantity, tally_pubkey) = match option {
+        PollOutcome::OptionA => (poll.option_a.quantity.get(), poll.option_a.tally_key),
+        PollOutcome::OptionB => (poll.option_b.quantity.get(), poll.option_b.tally_key),
+        _ => return Err(PollError::InvalidTallyKey.into()),
+    };
+
+    if winning_tally_key(poll)? == Some(tally_pubkey) {
+        Ok(quantity)
+    } else {
+        Err(PollError::CannotPayoutToLosers.into())
+    }
+}
+
+/// Returns the tally of the winning option, if there is one
+///
+/// Polls with a resolver are won by the submitted outcome, all other polls are
+/// won by the option with the most wagers.
+pub fn winning_tally_key(poll: &PollData) -> Result<Option<Pubkey>, ProgramError> {
+    if poll.has_resolver() {
+        return match PollOutcome::from(*poll.outcome) {
+            PollOutcome::OptionA => Ok(Some(poll.option_a.tally_key)),
+            PollOutcome::OptionB => Ok(Some(poll.option_b.tally_key)),
+            _ => Err(PollError::PollNotResolved.into()),
+        };
+    }
+
+    if poll.option_a.quantity.get() > poll.option_b.quantity.get() {
+        Ok(Some(poll.option_a.tally_key))
+    } else if poll.option_b.quantity.get() > poll.option_a.quantity.get() {
+        Ok(Some(poll.option_b.tally_key))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Whether anything was wagered on the winning option, so that there is someone to pay out
+pub fn has_winning_wagers(poll: &PollData) -> Result<bool, ProgramError> {
+    Ok(match winning_tally_key(poll)? {
+        Some(tally_key) if tally_key == poll.option_a.tally_key => poll.option_a.quantity.get() > 0,
+        Some(_) => poll.option_b.quantity.get() > 0,
+        None => false,
+    })
+}
+
+/// Wagers are refunded once a poll is cancelled, or once it is final without
+/// anything wagered on the winning option so that nobody can claim the pot
+pub fn expect_refundable(poll: &PollData, clock: &ClockData) -> ProgramResult {
+    if poll.is_cancelled() {
+        return Ok(());
+    }
+    expect_finished(poll, clock)?;
+    expect_finalized(poll, clock)?;
+    if has_winning_wagers(poll)? {
+        return Err(PollError::PollHasWinners.into());
+    }
+    Ok(())
+}
+
+/// Submitting the outcome opens the dispute window
+pub fn resolve(poll: &mut PollData, clock: &ClockData, outcome: PollOutcome) -> ProgramResult {
+    expect_not_cancelled(poll)?;
+    if PollOutcome::from(*poll.outcome) != PollOutcome::Unresolved {
+        return Err(PollError::PollAlreadyResolved.into());
+    }
+    match outcome {
+        PollOutcome::OptionA | PollOutcome::OptionB => {
+            *poll.outcome = outcome as u8;
+            poll.dispute.update(clock.slot, clock.unix_timestamp);
+            Ok(())
         }
-    } else if poll.option_b.tally_key == *tally_pubkey {
-        if *poll.option_b.quantity > *poll.option_a.quantity {
-            Ok(*poll.option_b.quantity)
-        } else {
-            Err(PollError::CannotPayoutToLosers.into())
+        _ => Err(PollError::InvalidInput.into()),
+    }
+}
+
+/// Resolved outcomes can be challenged until the dispute window after they were submitted
+pub fn expect_finalized(poll: &PollData, clock: &ClockData) -> ProgramResult {
+    expect_not_cancelled(poll)?;
+    if !poll.has_resolver() {
+        return Ok(());
+    }
+    if PollOutcome::from(*poll.outcome) == PollOutcome::Unresolved {
+        return Err(PollError::PollNotResolved.into());
+    }
+
+    match DisputeState::from(*poll.dispute.state) {
+        DisputeState::Settled => Ok(()),
+        DisputeState::Unchallenged if dispute_window_closed(poll, clock) => Ok(()),
+        _ => Err(PollError::PollNotFinalized.into()),
+    }
+}
+
+/// Whether the dispute window has passed since the outcome was submitted or challenged
+pub fn dispute_window_closed(poll: &PollData, clock: &ClockData) -> bool {
+    window_closed(
+        poll,
+        clock,
+        poll.dispute.updated_slot.get(),
+        poll.dispute.updated_time.get(),
+    )
+}
+
+/// Polls whose outcome is not submitted within the dispute window after the
+/// poll ends, or whose challenge is not settled within the window after it was
+/// made, are stalled and can be cancelled by anyone
+pub fn is_stalled(poll: &PollData, clock: &ClockData) -> bool {
+    if !poll.has_resolver() || poll.is_cancelled() {
+        return false;
+    }
+    match (
+        PollOutcome::from(*poll.outcome),
+        DisputeState::from(*poll.dispute.state),
+    ) {
+        (PollOutcome::Unresolved, _) => window_closed(poll, clock, poll.last_block, poll.end_time),
+        (_, DisputeState::Challenged) => dispute_window_closed(poll, clock),
+        _ => false,
+    }
+}
+
+/// The dispute window is measured in seconds for polls with a timestamp deadline
+///
+/// Windows that end past the largest representable time never close.
+fn window_closed(poll: &PollData, clock: &ClockData, slot: u64, unix_timestamp: i64) -> bool {
+    if poll.has_end_time() {
+        match unix_timestamp.checked_add(i64::from(poll.dispute.window)) {
+            Some(deadline) => deadline < clock.unix_timestamp,
+            None => false,
         }
     } else {
-        Err(PollError::InvalidTallyKey.into())
+        match slot.checked_add(u64::from(poll.dispute.window)) {
+            Some(deadline) => deadline < clock.slot,
+            None => false,
+        }
+    }
+}
+
+pub fn challenge(
+    poll: &mut PollData,
+    clock: &ClockData,
+    challenger_pubkey: &Pubkey,
+    stake: u64,
+) -> ProgramResult {
+    expect_not_cancelled(poll)?;
+    if PollOutcome::from(*poll.outcome) == PollOutcome::Unresolved {
+        return Err(PollError::PollNotResolved.into());
     }
+    if DisputeState::from(*poll.dispute.state) != DisputeState::Unchallenged
+        || dispute_window_closed(poll, clock)
+    {
+        return Err(PollError::DisputeNotAllowed.into());
+    }
+    if stake < MIN_DISPUTE_STAKE {
+        return Err(PollError::StakeBelowMinimum.into());
+    }
+
+    // The authority has another window to settle before the poll stalls
+    poll.dispute.challenge(challenger_pubkey, stake);
+    poll.dispute.update(clock.slot, clock.unix_timestamp);
+    Ok(())
+}
+
+/// Settles a challenge and returns whether the challenged outcome was overturned
+pub fn settle_dispute(poll: &mut PollData, outcome: PollOutcome) -> Result<bool, ProgramError> {
+    if DisputeState::from(*poll.dispute.state) != DisputeState::Challenged {
+        return Err(PollError::DisputeNotAllowed.into());
+    }
+    match outcome {
+        PollOutcome::OptionA | PollOutcome::OptionB => {}
+        _ => return Err(PollError::InvalidInput.into()),
+    }
+
+    let overturned = PollOutcome::from(*poll.outcome) != outcome;
+    *poll.outcome = outcome as u8;
+    poll.dispute.settle(overturned);
+    Ok(overturned)
 }
@@ -1,16 +1,74 @@
 use crate::result::PollError;
-use prediction_poll_data::TallyData;
-use solana_sdk::{account_info::AccountInfo, entrypoint::ProgramResult, pubkey::Pubkey};
-use std::convert::TryFrom;
-
-pub fn record_wager(tally: &mut TallyData, user_pubkey: &Pubkey, wager: u64) -> ProgramResult {
-    if let Some(wager_mut_ref) = tally.get_wager_mut(user_pubkey) {
-        let value = u64::from_le_bytes(*wager_mut_ref);
-        *wager_mut_ref = (value + wager).to_le_bytes();
-        return Ok(());
+use crate::util::credit;
+use prediction_poll_data::{share, PollOutcome, TallyData};
+use solana_sdk::{
+    account_info::AccountInfo, entrypoint::ProgramResult, program_error::ProgramError,
+    program_utils::next_account_info, pubkey::Pubkey,
+};
+use std::cell::RefMut;
+use std::slice::Iter;
+
+/// Takes the overflow pages that follow the head tally of a poll option
+pub fn next_pages<'a, 'b>(
+    head: &'a AccountInfo<'b>,
+    account_info_iter: &mut Iter<'a, AccountInfo<'b>>,
+    page_count: u32,
+) -> Result<Vec<&'a AccountInfo<'b>>, ProgramError> {
+    let mut pages = vec![head];
+    for _ in 1..page_count {
+        pages.push(next_account_info(account_info_iter)?);
+    }
+    Ok(pages)
+}
+
+/// Each page must link to the next one, so the head tally vouches for the whole chain
+pub fn load_pages<'a>(
+    pages: &[&AccountInfo],
+    pages_data: &'a mut [RefMut<&mut [u8]>],
+) -> Result<Vec<TallyData<'a>>, ProgramError> {
+    let tallies = pages_data
+        .iter_mut()
+        .map(|data| TallyData::from_bytes(&mut data[..]))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(PollError::from)?;
+    for (index, tally) in tallies.iter().enumerate() {
+        if tally.next_tally() != pages.get(index + 1).map(|page| *page.key) {
+            return Err(PollError::InvalidTallyPage.into());
+        }
     }
+    Ok(tallies)
+}
 
-    if tally.len() >= tally.capacity() {
+pub fn add_page(
+    tally: &mut TallyData,
+    poll_pubkey: &Pubkey,
+    next_pubkey: &Pubkey,
+) -> ProgramResult {
+    if tally.poll_key != *poll_pubkey {
+        Err(PollError::InvalidTallyKey.into())
+    } else if tally.next_tally().is_some() {
+        Err(PollError::TallyAlreadyExtended.into())
+    } else if !tally.is_full() {
+        Err(PollError::TallyNotFull.into())
+    } else {
+        tally.set_next_tally(next_pubkey);
+        Ok(())
+    }
+}
+
+/// Adds to an existing wager on any page, otherwise new voters go on the last page
+pub fn record_wager(pages: &mut [TallyData], user_pubkey: &Pubkey, wager: u64) -> ProgramResult {
+    for tally in pages.iter_mut() {
+        if let Some(wager_mut_ref) = tally.get_wager_mut(user_pubkey) {
+            let value = u64::from_le_bytes(*wager_mut_ref);
+            let value = value.checked_add(wager).ok_or(PollError::Overflow)?;
+            *wager_mut_ref = value.to_le_bytes();
+            return Ok(());
+        }
+    }
+
+    let tally = pages.last_mut().unwrap();
+    if tally.is_full() {
         Err(PollError::MaxTallyCapacity.into())
     } else {
         tally.add_tally(user_pubkey, wager);
@@ -18,31 +76,141 @@ pub fn record_wager(tally: &mut TallyData, user_pubkey: &Pubkey, wager: u64) ->
     }
 }
 
+/// Checks the voter's total wager across the pages of both options against the
+/// poll cap, zero for no cap
+pub fn expect_under_cap(
+    pages: &[TallyData],
+    other_pages: &[TallyData],
+    user_pubkey: &Pubkey,
+    wager: u64,
+    voter_cap: u64,
+) -> ProgramResult {
+    if voter_cap == 0 {
+        return Ok(());
+    }
+    let total = pages
+        .iter()
+        .chain(other_pages)
+        .filter_map(|tally| tally.position(user_pubkey).map(|index| tally.wager(index)))
+        .try_fold(wager, u64::checked_add)
+        .ok_or(PollError::Overflow)?;
+    if total > voter_cap {
+        return Err(PollError::VoterCapExceeded.into());
+    }
+    Ok(())
+}
+
+pub fn expect_no_wager(pages: &[TallyData], user_pubkey: &Pubkey) -> ProgramResult {
+    if pages
+        .iter()
+        .any(|tally| tally.position(user_pubkey).is_some())
+    {
+        return Err(PollError::HedgingNotAllowed.into());
+    }
+    Ok(())
+}
+
+/// Total wagered on a single page by voters who haven't claimed their payout yet
+pub fn unclaimed_quantity(tally: &TallyData) -> Result<u64, ProgramError> {
+    (0..tally.len())
+        .filter(|&index| !tally.is_claimed(index))
+        .try_fold(0, |total: u64, index| total.checked_add(tally.wager(index)))
+        .ok_or_else(|| PollError::Overflow.into())
+}
+
+pub fn expect_option(tally: &TallyData, poll_pubkey: &Pubkey) -> Result<PollOutcome, ProgramError> {
+    match tally.option {
+        PollOutcome::OptionA | PollOutcome::OptionB if tally.poll_key == *poll_pubkey => {
+            Ok(tally.option)
+        }
+        _ => Err(PollError::InvalidTallyKey.into()),
+    }
+}
+
+/// Splits the pot between the unclaimed wagers on a page, voters who already
+/// claimed their own payout are skipped
 pub fn payout(
-    tally: &TallyData,
+    tally: &mut TallyData,
     accounts: &[AccountInfo],
-    winning_quantity: u64,
+    unclaimed_quantity: u64,
     pot: u64,
 ) -> ProgramResult {
     if tally.len() != accounts.len() {
         return Err(PollError::InvalidPayoutList.into());
     }
+    let last = (0..tally.len())
+        .rev()
+        .find(|&index| !tally.is_claimed(index))
+        .ok_or(PollError::WagerAlreadyClaimed)?;
 
     let mut remaining = pot;
-    let pot = u128::from(pot);
-    let winning_quantity = u128::from(winning_quantity);
-    for (index, (key, wager)) in tally.iter().enumerate() {
-        if key != *accounts[index].key {
+    for (index, account) in accounts.iter().enumerate() {
+        if tally.key(index) != *account.key {
             return Err(PollError::InvalidPayoutList.into());
         }
+        if tally.is_claimed(index) {
+            continue;
+        }
+        tally.set_claimed(index);
 
-        let mut portion = u64::try_from(pot * u128::from(wager) / winning_quantity).unwrap();
-        remaining -= portion;
-        if index == accounts.len() - 1 {
-            portion += remaining; // last voter gets the rounding error
+        let wager = tally.wager(index);
+        let mut portion = share(pot, wager, unclaimed_quantity).ok_or(PollError::Overflow)?;
+        remaining = remaining.checked_sub(portion).ok_or(PollError::Overflow)?;
+        if index == last {
+            portion += remaining; // last voter gets the rounding error, at most the pot
         }
-        **accounts[index].lamports.borrow_mut() += portion;
+        credit(account, portion)?;
     }
 
     Ok(())
 }
+
+/// Returns every wager in the tally that wasn't refunded yet and the total refunded
+pub fn refund(tally: &mut TallyData, accounts: &[AccountInfo]) -> Result<u64, ProgramError> {
+    if tally.len() != accounts.len() {
+        return Err(PollError::InvalidPayoutList.into());
+    }
+
+    let mut refunded: u64 = 0;
+    for (index, account) in accounts.iter().enumerate() {
+        if tally.key(index) != *account.key {
+            return Err(PollError::InvalidPayoutList.into());
+        }
+        if tally.is_claimed(index) {
+            continue;
+        }
+        tally.set_claimed(index);
+
+        let wager = tally.wager(index);
+        credit(account, wager)?;
+        refunded = refunded.checked_add(wager).ok_or(PollError::Overflow)?;
+    }
+
+    Ok(refunded)
+}
+
+/// Marks a single voter's wager as claimed and returns their share of the pot
+pub fn claim(
+    tally: &mut TallyData,
+    user_pubkey: &Pubkey,
+    winning_quantity: u64,
+    pot: u64,
+) -> Result<u64, ProgramError> {
+    let index = tally
+        .position(user_pubkey)
+        .ok_or(PollError::WagerNotFound)?;
+    if tally.is_claimed(index) {
+        return Err(PollError::WagerAlreadyClaimed.into());
+    }
+    tally.set_claimed(index);
+
+    share(pot, tally.wager(index), winning_quantity).ok_or_else(|| PollError::Overflow.into())
+}
+
+pub fn expect_all_claimed(tally: &TallyData) -> ProgramResult {
+    if (0..tally.len()).all(|index| tally.is_claimed(index)) {
+        Ok(())
+    } else {
+        Err(PollError::PollNotSettled.into())
+    }
+}
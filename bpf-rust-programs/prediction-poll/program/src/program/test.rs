@@ -0,0 +1,1333 @@
+//! In-process harness that drives `process_instruction` with native accounts
+
+use super::process_instruction;
+use crate::result::PollError;
+use prediction_poll_data::{
+    fees, CollectionData, CommandData, DataType, Encode, InitPollData, PollData, PollOutcome,
+    TallyData, MAX_FEE_BPS, MIN_COLLECTION_SIZE, MIN_DISPUTE_STAKE, MIN_TALLY_SIZE,
+    PROTOCOL_FEE_BPS, PROTOCOL_TREASURY,
+};
+use proptest::prelude::*;
+use solana_sdk::{
+    account_info::AccountInfo, entrypoint::ProgramResult, program_error::ProgramError,
+    pubkey::Pubkey, sysvar::clock,
+};
+
+const POLL_SIZE: usize = 512;
+const POLL_RENT: u64 = 1000;
+const TALLY_CAPACITY: usize = 16;
+const TALLY_SIZE: usize = MIN_TALLY_SIZE + 41 * (TALLY_CAPACITY - 1);
+
+struct TestAccount {
+    key: Pubkey,
+    is_signer: bool,
+    lamports: u64,
+    data: Vec<u8>,
+    owner: Pubkey,
+}
+
+impl TestAccount {
+    fn new(seed: u8, owner: &Pubkey, lamports: u64, size: usize) -> Self {
+        Self {
+            key: Pubkey::new(&[seed; 32]),
+            is_signer: false,
+            lamports,
+            data: vec![0; size],
+            owner: *owner,
+        }
+    }
+
+    fn signer(mut self) -> Self {
+        self.is_signer = true;
+        self
+    }
+
+    fn info(&mut self) -> AccountInfo<'_> {
+        AccountInfo::new(
+            &self.key,
+            self.is_signer,
+            true,
+            &mut self.lamports,
+            &mut self.data,
+            &self.owner,
+            false,
+            0,
+        )
+    }
+}
+
+/// Runs an instruction, rolling back every account if it fails like the runtime would
+fn process(program_id: &Pubkey, accounts: &mut [&mut TestAccount], data: &[u8]) -> ProgramResult {
+    let snapshot: Vec<_> = accounts
+        .iter()
+        .map(|account| (account.lamports, account.data.clone()))
+        .collect();
+    let result = {
+        let infos: Vec<_> = accounts.iter_mut().map(|account| account.info()).collect();
+        process_instruction(program_id, &infos, data)
+    };
+    if result.is_err() {
+        for (account, (lamports, data)) in accounts.iter_mut().zip(snapshot) {
+            account.lamports = lamports;
+            account.data = data;
+        }
+    }
+    result
+}
+
+fn init_poll_data(timeout: u32, creator_fee_bps: u16) -> InitPollData<'static> {
+    let header = b"Who wins?";
+    let option_a = b"Team A";
+    let option_b = b"Team B";
+    InitPollData {
+        timeout,
+        start_slot: 0,
+        end_time: 0,
+        no_hedging: false,
+        min_wager: 0,
+        max_wager: 0,
+        voter_cap: 0,
+        creator_fee_bps,
+        resolver_key: Pubkey::default(),
+        dispute_window: 0,
+        authority_key: Pubkey::default(),
+        header_len: header.len() as u32,
+        header,
+        option_a_len: option_a.len() as u32,
+        option_a,
+        option_b_len: option_b.len() as u32,
+        option_b,
+    }
+}
+
+/// A collection holding one poll, with the poll creator, treasury and clock sysvar
+struct TestPoll {
+    program_id: Pubkey,
+    clock: TestAccount,
+    collection: TestAccount,
+    creator: TestAccount,
+    treasury: TestAccount,
+    resolver: TestAccount,
+    authority: TestAccount,
+    poll: TestAccount,
+    tally_a: TestAccount,
+    tally_b: TestAccount,
+    next_seed: u8,
+}
+
+impl TestPoll {
+    fn new() -> Self {
+        let program_id = Pubkey::new(&[1; 32]);
+        let mut clock = TestAccount::new(2, &Pubkey::default(), 1, 40);
+        clock.key = clock::id();
+        let mut treasury = TestAccount::new(5, &program_id, 0, 0);
+        treasury.key = Pubkey::new(&PROTOCOL_TREASURY);
+        Self {
+            clock,
+            collection: TestAccount::new(3, &program_id, 0, MIN_COLLECTION_SIZE).signer(),
+            creator: TestAccount::new(4, &program_id, 0, 0).signer(),
+            treasury,
+            resolver: TestAccount::new(9, &Pubkey::default(), 0, 0).signer(),
+            authority: TestAccount::new(10, &Pubkey::default(), 0, 0).signer(),
+            poll: TestAccount::new(6, &program_id, POLL_RENT, POLL_SIZE).signer(),
+            tally_a: TestAccount::new(7, &program_id, 0, TALLY_SIZE).signer(),
+            tally_b: TestAccount::new(8, &program_id, 0, TALLY_SIZE).signer(),
+            program_id,
+            next_seed: 100,
+        }
+    }
+
+    /// Creates the collection and a poll that ends `timeout` slots from now
+    fn create(protocol_fee_bps: u16, timeout: u32, creator_fee_bps: u16) -> Self {
+        let mut test = Self::new();
+        test.init_collection().unwrap();
+        test.init_poll(&init_poll_data(timeout, creator_fee_bps).to_bytes())
+            .unwrap();
+        test.set_protocol_fee(protocol_fee_bps);
+        test
+    }
+
+    /// Charges the poll a protocol fee as if the program was built with it
+    fn set_protocol_fee(&mut self, protocol_fee_bps: u16) {
+        let mut data = self.poll.data.clone();
+        let mut poll = PollData::from_bytes(&mut data).unwrap();
+        poll.protocol_fee_bps = protocol_fee_bps;
+        poll.encode(&mut self.poll.data).unwrap();
+    }
+
+    /// Creates a poll without fees whose outcome is submitted by the resolver
+    fn create_resolved(timeout: u32, dispute_window: u32) -> Self {
+        let mut test = Self::new();
+        test.init_collection().unwrap();
+        let init = InitPollData {
+            resolver_key: test.resolver.key,
+            dispute_window,
+            authority_key: test.authority.key,
+            ..init_poll_data(timeout, 0)
+        };
+        test.init_poll(&init.to_bytes()).unwrap();
+        test
+    }
+
+    fn set_slot(&mut self, slot: u64) {
+        self.clock.data[..8].copy_from_slice(&slot.to_le_bytes());
+    }
+
+    fn set_unix_timestamp(&mut self, unix_timestamp: i64) {
+        self.clock.data[32..].copy_from_slice(&unix_timestamp.to_le_bytes());
+    }
+
+    /// Account created by the voter to hold a wager until it is drained by a vote
+    fn wager_account(&mut self, lamports: u64) -> TestAccount {
+        self.next_seed += 1;
+        TestAccount::new(self.next_seed, &self.program_id, lamports, 0).signer()
+    }
+
+    fn voter(&mut self) -> TestAccount {
+        self.next_seed += 1;
+        TestAccount::new(self.next_seed, &Pubkey::default(), 0, 0)
+    }
+
+    fn init_collection(&mut self) -> ProgramResult {
+        process(
+            &self.program_id,
+            &mut [&mut self.collection],
+            &[CommandData::InitCollection as u8],
+        )
+    }
+
+    fn init_poll(&mut self, init_data: &[u8]) -> ProgramResult {
+        let mut data = vec![CommandData::InitPoll as u8];
+        data.extend_from_slice(init_data);
+        process(
+            &self.program_id,
+            &mut [
+                &mut self.creator,
+                &mut self.poll,
+                &mut self.collection,
+                &mut self.tally_a,
+                &mut self.tally_b,
+                &mut self.clock,
+            ],
+            &data,
+        )
+    }
+
+    fn vote(&mut self, option: PollOutcome, voter: &mut TestAccount, wager: u64) -> ProgramResult {
+        let mut wager_account = self.wager_account(wager);
+        let (tally, other_tally) = match option {
+            PollOutcome::OptionA => (&mut self.tally_a, &mut self.tally_b),
+            _ => (&mut self.tally_b, &mut self.tally_a),
+        };
+        // The other tally is only read when the poll forbids hedging or caps voters
+        process(
+            &self.program_id,
+            &mut [
+                &mut wager_account,
+                &mut self.poll,
+                tally,
+                voter,
+                &mut self.clock,
+                other_tally,
+            ],
+            &[CommandData::SubmitVote as u8],
+        )
+    }
+
+    fn claim(&mut self, option: PollOutcome, voters: &mut [&mut TestAccount]) -> ProgramResult {
+        let tally = match option {
+            PollOutcome::OptionA => &mut self.tally_a,
+            _ => &mut self.tally_b,
+        };
+        let mut accounts: Vec<&mut TestAccount> = vec![
+            &mut self.poll,
+            tally,
+            &mut self.clock,
+            &mut self.creator,
+            &mut self.treasury,
+        ];
+        accounts.extend(voters.iter_mut().map(|voter| &mut **voter));
+        process(
+            &self.program_id,
+            &mut accounts,
+            &[CommandData::SubmitClaim as u8],
+        )
+    }
+
+    /// Claims a single winner's payout, signed by the voter
+    fn claim_payout(&mut self, option: PollOutcome, voter: &mut TestAccount) -> ProgramResult {
+        let tally = match option {
+            PollOutcome::OptionA => &mut self.tally_a,
+            _ => &mut self.tally_b,
+        };
+        process(
+            &self.program_id,
+            &mut [
+                voter,
+                &mut self.poll,
+                tally,
+                &mut self.clock,
+                &mut self.creator,
+                &mut self.treasury,
+            ],
+            &[CommandData::ClaimPayout as u8],
+        )
+    }
+
+    /// New account for a tally page, created by the client before it is linked
+    fn page(&mut self) -> TestAccount {
+        self.next_seed += 1;
+        TestAccount::new(self.next_seed, &self.program_id, 0, TALLY_SIZE).signer()
+    }
+
+    fn add_page(&mut self, option: PollOutcome, page: &mut TestAccount) -> ProgramResult {
+        let tally = match option {
+            PollOutcome::OptionA => &mut self.tally_a,
+            _ => &mut self.tally_b,
+        };
+        process(
+            &self.program_id,
+            &mut [page, &mut self.poll, tally],
+            &[CommandData::AddTallyPage as u8],
+        )
+    }
+
+    fn extend_collection(&mut self, next_collection: &mut TestAccount) -> ProgramResult {
+        process(
+            &self.program_id,
+            &mut [next_collection, &mut self.collection],
+            &[CommandData::ExtendCollection as u8],
+        )
+    }
+
+    fn challenge(&mut self, challenger: &mut TestAccount, stake: u64) -> ProgramResult {
+        let mut stake_account = self.wager_account(stake);
+        process(
+            &self.program_id,
+            &mut [
+                &mut stake_account,
+                &mut self.poll,
+                challenger,
+                &mut self.clock,
+            ],
+            &[CommandData::ChallengeOutcome as u8],
+        )
+    }
+
+    fn settle(&mut self, outcome: PollOutcome, challenger: &mut TestAccount) -> ProgramResult {
+        process(
+            &self.program_id,
+            &mut [&mut self.authority, &mut self.poll, challenger],
+            &[CommandData::SettleDispute as u8, outcome as u8],
+        )
+    }
+
+    /// Cancels the poll, the challenger to refund follows the clock
+    fn cancel(&mut self, challenger: Option<&mut TestAccount>) -> ProgramResult {
+        let mut accounts: Vec<&mut TestAccount> =
+            vec![&mut self.creator, &mut self.poll, &mut self.clock];
+        accounts.extend(challenger);
+        process(
+            &self.program_id,
+            &mut accounts,
+            &[CommandData::CancelPoll as u8],
+        )
+    }
+
+    /// Refunds the wagers on a tally page, no signer needed
+    fn refund(&mut self, option: PollOutcome, voters: &mut [&mut TestAccount]) -> ProgramResult {
+        let tally = match option {
+            PollOutcome::OptionA => &mut self.tally_a,
+            _ => &mut self.tally_b,
+        };
+        let mut accounts: Vec<&mut TestAccount> = vec![&mut self.poll, tally, &mut self.clock];
+        accounts.extend(voters.iter_mut().map(|voter| &mut **voter));
+        process(
+            &self.program_id,
+            &mut accounts,
+            &[CommandData::RefundWagers as u8],
+        )
+    }
+
+    fn resolve(&mut self, outcome: PollOutcome) -> ProgramResult {
+        process(
+            &self.program_id,
+            &mut [&mut self.resolver, &mut self.poll, &mut self.clock],
+            &[CommandData::ResolvePoll as u8, outcome as u8],
+        )
+    }
+
+    fn close(&mut self) -> ProgramResult {
+        process(
+            &self.program_id,
+            &mut [
+                &mut self.creator,
+                &mut self.poll,
+                &mut self.collection,
+                &mut self.tally_a,
+                &mut self.tally_b,
+                &mut self.clock,
+            ],
+            &[CommandData::ClosePoll as u8],
+        )
+    }
+
+    fn quantities(&mut self) -> (u64, u64) {
+        let poll = PollData::from_bytes(&mut self.poll.data).unwrap();
+        (poll.option_a.quantity.get(), poll.option_b.quantity.get())
+    }
+}
+
+fn poll_error(error: PollError) -> ProgramResult {
+    Err(ProgramError::from(error))
+}
+
+#[test]
+pub fn init_poll() {
+    let mut test = TestPoll::new();
+    assert_eq!(test.init_poll(&[]), poll_error(PollError::InvalidDataType));
+    test.init_collection().unwrap();
+    assert_eq!(test.init_collection(), poll_error(PollError::AccountNotNew));
+
+    test.set_slot(50);
+    let init_data = init_poll_data(10, 200).to_bytes();
+    assert_eq!(
+        test.init_poll(&init_data[..init_data.len() - 1]),
+        poll_error(PollError::InvalidInput)
+    );
+    assert_eq!(
+        test.init_poll(&init_poll_data(10, MAX_FEE_BPS - PROTOCOL_FEE_BPS + 1).to_bytes()),
+        poll_error(PollError::InvalidInput)
+    );
+    test.init_poll(&init_data).unwrap();
+
+    let poll = PollData::from_bytes(&mut test.poll.data).unwrap();
+    assert_eq!(poll.creator_key, test.creator.key);
+    assert_eq!(poll.last_block, 60);
+    assert_eq!(poll.protocol_fee_bps, PROTOCOL_FEE_BPS);
+    assert_eq!(poll.option_a.tally_key, test.tally_a.key);
+    let tally = TallyData::from_bytes(&mut test.tally_b.data).unwrap();
+    assert_eq!(tally.poll_key, test.poll.key);
+    assert!(tally.option == PollOutcome::OptionB);
+}
+
+#[test]
+pub fn end_time_in_future() {
+    let mut test = TestPoll::new();
+    test.init_collection().unwrap();
+    test.set_unix_timestamp(1_600_000_000);
+    for end_time in [-1, 1_599_999_999, 1_600_000_000].iter() {
+        let init = InitPollData {
+            end_time: *end_time,
+            ..init_poll_data(10, 0)
+        };
+        assert_eq!(
+            test.init_poll(&init.to_bytes()),
+            poll_error(PollError::InvalidInput)
+        );
+    }
+
+    let init = InitPollData {
+        end_time: 1_600_000_001,
+        ..init_poll_data(10, 0)
+    };
+    test.init_poll(&init.to_bytes()).unwrap();
+    let poll = PollData::from_bytes(&mut test.poll.data).unwrap();
+    assert_eq!(poll.end_time, 1_600_000_001);
+}
+
+#[test]
+pub fn vote_and_claim() {
+    let mut test = TestPoll::create(100, 10, 200);
+    let mut voter_1 = test.voter();
+    let mut voter_2 = test.voter();
+    let mut voter_3 = test.voter();
+
+    test.vote(PollOutcome::OptionA, &mut voter_1, 300).unwrap();
+    test.vote(PollOutcome::OptionB, &mut voter_2, 100).unwrap();
+    test.vote(PollOutcome::OptionA, &mut voter_3, 100).unwrap();
+    assert_eq!(test.quantities(), (400, 100));
+    assert_eq!(test.poll.lamports, POLL_RENT + 500);
+
+    assert_eq!(
+        test.claim(PollOutcome::OptionA, &mut [&mut voter_1, &mut voter_3]),
+        poll_error(PollError::PollNotFinished)
+    );
+
+    test.set_slot(11);
+    assert_eq!(
+        test.vote(PollOutcome::OptionB, &mut voter_2, 100),
+        poll_error(PollError::PollAlreadyFinished)
+    );
+    assert_eq!(
+        test.claim(PollOutcome::OptionB, &mut [&mut voter_2]),
+        poll_error(PollError::CannotPayoutToLosers)
+    );
+    assert_eq!(
+        test.claim(PollOutcome::OptionA, &mut [&mut voter_3, &mut voter_1]),
+        poll_error(PollError::InvalidPayoutList)
+    );
+
+    test.treasury.key = test.creator.key;
+    assert_eq!(
+        test.claim(PollOutcome::OptionA, &mut [&mut voter_1, &mut voter_3]),
+        poll_error(PollError::InvalidKey)
+    );
+    test.treasury.key = Pubkey::new(&PROTOCOL_TREASURY);
+
+    // A pot of 500 less 3% in fees, split 3:1 with the rounding error going last
+    test.claim(PollOutcome::OptionA, &mut [&mut voter_1, &mut voter_3])
+        .unwrap();
+    assert_eq!(voter_1.lamports, 363);
+    assert_eq!(voter_3.lamports, 122);
+    assert_eq!(voter_2.lamports, 0);
+    assert_eq!(test.creator.lamports, 10);
+    assert_eq!(test.treasury.lamports, 5);
+    assert_eq!(test.poll.lamports, POLL_RENT);
+
+    assert_eq!(
+        test.claim(PollOutcome::OptionA, &mut [&mut voter_1, &mut voter_3]),
+        poll_error(PollError::WagerAlreadyClaimed)
+    );
+}
+
+#[test]
+pub fn rejected_votes() {
+    let mut test = TestPoll::create(0, 10, 0);
+    let mut voter_1 = test.voter();
+    let mut voter_2 = test.voter();
+
+    assert_eq!(
+        test.vote(PollOutcome::OptionA, &mut voter_1, 0),
+        poll_error(PollError::WagerHasNoFunds)
+    );
+    test.vote(PollOutcome::OptionA, &mut voter_1, 100).unwrap();
+    assert_eq!(
+        test.vote(PollOutcome::OptionB, &mut voter_2, 100),
+        poll_error(PollError::PollCannotBeEven)
+    );
+    assert_eq!(
+        test.vote(PollOutcome::OptionA, &mut voter_1, u64::MAX),
+        poll_error(PollError::Overflow)
+    );
+    assert_eq!(test.quantities(), (100, 0));
+    assert_eq!(test.poll.lamports, POLL_RENT + 100);
+
+    assert_eq!(
+        process(&test.program_id, &mut [], &[42]),
+        poll_error(PollError::InvalidCommand)
+    );
+    assert_eq!(
+        process(&test.program_id, &mut [], &[]),
+        poll_error(PollError::InvalidInput)
+    );
+}
+
+#[test]
+pub fn voter_cap_spans_both_options() {
+    let mut test = TestPoll::new();
+    test.init_collection().unwrap();
+    let init = InitPollData {
+        voter_cap: 150,
+        ..init_poll_data(10, 0)
+    };
+    test.init_poll(&init.to_bytes()).unwrap();
+    let mut voter_1 = test.voter();
+    let mut voter_2 = test.voter();
+
+    test.vote(PollOutcome::OptionA, &mut voter_1, 100).unwrap();
+    assert_eq!(
+        test.vote(PollOutcome::OptionB, &mut voter_1, 60),
+        poll_error(PollError::VoterCapExceeded)
+    );
+    test.vote(PollOutcome::OptionB, &mut voter_1, 50).unwrap();
+    assert_eq!(
+        test.vote(PollOutcome::OptionA, &mut voter_1, 1),
+        poll_error(PollError::VoterCapExceeded)
+    );
+    test.vote(PollOutcome::OptionB, &mut voter_2, 150).unwrap();
+    assert_eq!(test.quantities(), (100, 200));
+}
+
+#[test]
+pub fn claim_payout_once() {
+    let mut test = TestPoll::create(100, 10, 200);
+    let mut voter_1 = test.voter().signer();
+    let mut voter_2 = test.voter().signer();
+    let mut voter_3 = test.voter().signer();
+    test.vote(PollOutcome::OptionA, &mut voter_1, 300).unwrap();
+    test.vote(PollOutcome::OptionB, &mut voter_2, 100).unwrap();
+    test.vote(PollOutcome::OptionA, &mut voter_3, 100).unwrap();
+
+    assert_eq!(
+        test.claim_payout(PollOutcome::OptionA, &mut voter_1),
+        poll_error(PollError::PollNotFinished)
+    );
+    test.set_slot(11);
+    assert_eq!(
+        test.claim_payout(PollOutcome::OptionB, &mut voter_2),
+        poll_error(PollError::CannotPayoutToLosers)
+    );
+    assert_eq!(
+        test.claim_payout(PollOutcome::OptionA, &mut voter_2),
+        poll_error(PollError::WagerNotFound)
+    );
+    voter_1.is_signer = false;
+    assert_eq!(
+        test.claim_payout(PollOutcome::OptionA, &mut voter_1),
+        Err(ProgramError::MissingRequiredSignature)
+    );
+
+    voter_1.is_signer = true;
+
+    // Protocol fees only go to the program's treasury
+    test.treasury.key = test.creator.key;
+    assert_eq!(
+        test.claim_payout(PollOutcome::OptionA, &mut voter_1),
+        poll_error(PollError::InvalidKey)
+    );
+    test.treasury.key = Pubkey::new(&PROTOCOL_TREASURY);
+
+    // Each winner pays their part of the 3% fees on a 3:1 split of 500
+    test.claim_payout(PollOutcome::OptionA, &mut voter_1)
+        .unwrap();
+    assert_eq!(voter_1.lamports, 365);
+    assert_eq!(test.creator.lamports, 7);
+    assert_eq!(test.treasury.lamports, 3);
+    assert_eq!(
+        test.claim_payout(PollOutcome::OptionA, &mut voter_1),
+        poll_error(PollError::WagerAlreadyClaimed)
+    );
+
+    test.claim_payout(PollOutcome::OptionA, &mut voter_3)
+        .unwrap();
+    assert_eq!(voter_3.lamports, 122);
+    assert_eq!(test.creator.lamports, 9);
+    assert_eq!(test.treasury.lamports, 4);
+    assert_eq!(test.poll.lamports, POLL_RENT);
+    assert_eq!(
+        test.claim(PollOutcome::OptionA, &mut [&mut voter_1, &mut voter_3]),
+        poll_error(PollError::WagerAlreadyClaimed)
+    );
+}
+
+#[test]
+pub fn claim_page_after_payout() {
+    let mut test = TestPoll::create(100, 10, 200);
+    let mut voter_1 = test.voter().signer();
+    let mut voter_2 = test.voter();
+    let mut voter_3 = test.voter();
+    test.vote(PollOutcome::OptionA, &mut voter_1, 300).unwrap();
+    test.vote(PollOutcome::OptionB, &mut voter_2, 100).unwrap();
+    test.vote(PollOutcome::OptionA, &mut voter_3, 100).unwrap();
+
+    test.set_slot(11);
+    test.claim_payout(PollOutcome::OptionA, &mut voter_1)
+        .unwrap();
+    assert_eq!(voter_1.lamports, 365);
+
+    // The page pays the voters left on it the same as if they claimed their own
+    test.claim(PollOutcome::OptionA, &mut [&mut voter_1, &mut voter_3])
+        .unwrap();
+    assert_eq!(voter_1.lamports, 365);
+    assert_eq!(voter_3.lamports, 122);
+    assert_eq!(test.creator.lamports, 9);
+    assert_eq!(test.treasury.lamports, 4);
+    assert_eq!(test.poll.lamports, POLL_RENT);
+    assert_eq!(
+        test.claim(PollOutcome::OptionA, &mut [&mut voter_1, &mut voter_3]),
+        poll_error(PollError::WagerAlreadyClaimed)
+    );
+}
+
+#[test]
+pub fn cancel_refunds_wagers() {
+    let mut test = TestPoll::create(0, 10, 0);
+    let mut voter_1 = test.voter();
+    let mut voter_2 = test.voter();
+    test.vote(PollOutcome::OptionA, &mut voter_1, 100).unwrap();
+    test.vote(PollOutcome::OptionB, &mut voter_2, 50).unwrap();
+    test.vote(PollOutcome::OptionA, &mut voter_1, 20).unwrap();
+
+    test.creator.is_signer = false;
+    assert_eq!(
+        test.cancel(None),
+        Err(ProgramError::MissingRequiredSignature)
+    );
+    test.creator.is_signer = true;
+    assert_eq!(
+        test.refund(PollOutcome::OptionA, &mut [&mut voter_1]),
+        poll_error(PollError::PollNotFinished)
+    );
+
+    test.cancel(None).unwrap();
+    assert_eq!(
+        test.vote(PollOutcome::OptionA, &mut voter_1, 100),
+        poll_error(PollError::PollCancelled)
+    );
+    assert_eq!(test.cancel(None), poll_error(PollError::PollCancelled));
+
+    // Each page is refunded on its own, refunding a page again pays nothing
+    assert_eq!(
+        test.refund(PollOutcome::OptionA, &mut [&mut voter_2]),
+        poll_error(PollError::InvalidPayoutList)
+    );
+    test.refund(PollOutcome::OptionA, &mut [&mut voter_1])
+        .unwrap();
+    test.refund(PollOutcome::OptionA, &mut [&mut voter_1])
+        .unwrap();
+    assert_eq!(voter_1.lamports, 120);
+    assert_eq!(test.close(), poll_error(PollError::PollNotSettled));
+    test.refund(PollOutcome::OptionB, &mut [&mut voter_2])
+        .unwrap();
+    assert_eq!(voter_2.lamports, 50);
+    assert_eq!(test.poll.lamports, POLL_RENT);
+
+    // Cancelled polls can be closed once refunded
+    test.close().unwrap();
+    assert_eq!(test.creator.lamports, POLL_RENT);
+
+    // The creator can no longer cancel once the poll has ended
+    let mut test = TestPoll::create(0, 10, 0);
+    test.set_slot(11);
+    assert_eq!(
+        test.cancel(None),
+        poll_error(PollError::PollAlreadyFinished)
+    );
+}
+
+#[test]
+pub fn close_claimed_poll() {
+    let mut test = TestPoll::create(0, 10, 0);
+    let mut voter_1 = test.voter().signer();
+    let mut voter_2 = test.voter();
+    test.vote(PollOutcome::OptionA, &mut voter_1, 100).unwrap();
+    test.vote(PollOutcome::OptionB, &mut voter_2, 50).unwrap();
+
+    assert_eq!(test.close(), poll_error(PollError::PollNotFinished));
+    test.set_slot(11);
+    assert_eq!(test.close(), poll_error(PollError::PollNotSettled));
+    assert_eq!(
+        test.refund(PollOutcome::OptionB, &mut [&mut voter_2]),
+        poll_error(PollError::PollHasWinners)
+    );
+
+    test.claim_payout(PollOutcome::OptionA, &mut voter_1)
+        .unwrap();
+    test.creator.is_signer = false;
+    assert_eq!(test.close(), Err(ProgramError::MissingRequiredSignature));
+    test.creator.is_signer = true;
+    test.close().unwrap();
+    assert_eq!(voter_1.lamports, 150);
+    assert_eq!(test.creator.lamports, POLL_RENT);
+    assert_eq!(test.poll.lamports, 0);
+    let collection = CollectionData::from_bytes(&mut test.collection.data).unwrap();
+    assert!(collection.is_empty());
+}
+
+#[test]
+pub fn resolver_needs_dispute_authority() {
+    let mut test = TestPoll::new();
+    test.init_collection().unwrap();
+    let init = InitPollData {
+        resolver_key: test.resolver.key,
+        dispute_window: 5,
+        ..init_poll_data(10, 0)
+    };
+    assert_eq!(
+        test.init_poll(&init.to_bytes()),
+        poll_error(PollError::MissingDisputeAuthority)
+    );
+    let init = InitPollData {
+        authority_key: test.authority.key,
+        dispute_window: 0,
+        ..init
+    };
+    assert_eq!(
+        test.init_poll(&init.to_bytes()),
+        poll_error(PollError::MissingDisputeAuthority)
+    );
+}
+
+#[test]
+pub fn dispute_window_starts_at_resolution() {
+    let mut test = TestPoll::create_resolved(10, 5);
+    let mut voter_1 = test.voter();
+    let mut voter_2 = test.voter();
+    let mut challenger = test.voter();
+    test.vote(PollOutcome::OptionA, &mut voter_1, 100).unwrap();
+    test.vote(PollOutcome::OptionB, &mut voter_2, 50).unwrap();
+
+    // A late outcome can still be challenged for a full window
+    test.set_slot(30);
+    test.resolve(PollOutcome::OptionA).unwrap();
+    test.set_slot(35);
+    assert_eq!(
+        test.claim(PollOutcome::OptionA, &mut [&mut voter_1]),
+        poll_error(PollError::PollNotFinalized)
+    );
+    assert_eq!(
+        test.challenge(&mut challenger, MIN_DISPUTE_STAKE - 1),
+        poll_error(PollError::StakeBelowMinimum)
+    );
+
+    test.set_slot(36);
+    assert_eq!(
+        test.challenge(&mut challenger, MIN_DISPUTE_STAKE),
+        poll_error(PollError::DisputeNotAllowed)
+    );
+    test.claim(PollOutcome::OptionA, &mut [&mut voter_1])
+        .unwrap();
+    assert_eq!(voter_1.lamports, 150);
+}
+
+#[test]
+pub fn overturned_outcome_pays_new_winners() {
+    let mut test = TestPoll::create_resolved(10, 5);
+    let mut voter_1 = test.voter();
+    let mut voter_2 = test.voter();
+    let mut challenger = test.voter();
+    test.vote(PollOutcome::OptionA, &mut voter_1, 100).unwrap();
+    test.vote(PollOutcome::OptionB, &mut voter_2, 50).unwrap();
+
+    assert_eq!(
+        test.resolve(PollOutcome::OptionA),
+        poll_error(PollError::PollNotFinished)
+    );
+    test.set_slot(11);
+    test.resolver.is_signer = false;
+    assert_eq!(
+        test.resolve(PollOutcome::OptionA),
+        Err(ProgramError::MissingRequiredSignature)
+    );
+    test.resolver.is_signer = true;
+    assert_eq!(
+        test.challenge(&mut challenger, MIN_DISPUTE_STAKE),
+        poll_error(PollError::PollNotResolved)
+    );
+    test.resolve(PollOutcome::OptionA).unwrap();
+    assert_eq!(
+        test.resolve(PollOutcome::OptionB),
+        poll_error(PollError::PollAlreadyResolved)
+    );
+
+    test.challenge(&mut challenger, MIN_DISPUTE_STAKE).unwrap();
+    assert_eq!(
+        test.challenge(&mut challenger, MIN_DISPUTE_STAKE),
+        poll_error(PollError::DisputeNotAllowed)
+    );
+    test.authority.is_signer = false;
+    assert_eq!(
+        test.settle(PollOutcome::OptionB, &mut challenger),
+        Err(ProgramError::MissingRequiredSignature)
+    );
+
+    // Overturning the outcome refunds the stake and pays the other option
+    test.authority.is_signer = true;
+    test.settle(PollOutcome::OptionB, &mut challenger).unwrap();
+    assert_eq!(challenger.lamports, MIN_DISPUTE_STAKE);
+    assert_eq!(
+        test.claim(PollOutcome::OptionA, &mut [&mut voter_1]),
+        poll_error(PollError::CannotPayoutToLosers)
+    );
+    test.claim(PollOutcome::OptionB, &mut [&mut voter_2])
+        .unwrap();
+    assert_eq!(voter_2.lamports, 150);
+    assert_eq!(test.poll.lamports, POLL_RENT);
+}
+
+#[test]
+pub fn upheld_stake_goes_to_winners() {
+    let mut test = TestPoll::create_resolved(10, 5);
+    let mut voter_1 = test.voter();
+    let mut voter_2 = test.voter();
+    let mut challenger = test.voter();
+    test.vote(PollOutcome::OptionA, &mut voter_1, 100).unwrap();
+    test.vote(PollOutcome::OptionB, &mut voter_2, 50).unwrap();
+
+    test.set_slot(11);
+    test.resolve(PollOutcome::OptionA).unwrap();
+    test.challenge(&mut challenger, MIN_DISPUTE_STAKE).unwrap();
+    test.set_slot(20);
+    assert_eq!(
+        test.claim(PollOutcome::OptionA, &mut [&mut voter_1]),
+        poll_error(PollError::PollNotFinalized)
+    );
+
+    test.settle(PollOutcome::OptionA, &mut challenger).unwrap();
+    assert_eq!(challenger.lamports, 0);
+    test.claim(PollOutcome::OptionA, &mut [&mut voter_1])
+        .unwrap();
+    assert_eq!(voter_1.lamports, 150 + MIN_DISPUTE_STAKE);
+    assert_eq!(test.poll.lamports, POLL_RENT);
+}
+
+#[test]
+pub fn cancel_stalled_poll() {
+    let mut test = TestPoll::create_resolved(10, 5);
+    let mut voter_1 = test.voter();
+    let mut voter_2 = test.voter();
+    let mut challenger = test.voter();
+    test.vote(PollOutcome::OptionA, &mut voter_1, 100).unwrap();
+    test.vote(PollOutcome::OptionB, &mut voter_2, 50).unwrap();
+
+    test.set_slot(11);
+    test.resolve(PollOutcome::OptionA).unwrap();
+    test.challenge(&mut challenger, MIN_DISPUTE_STAKE).unwrap();
+
+    // Only the creator can cancel until the authority misses its window
+    test.creator.is_signer = false;
+    test.set_slot(16);
+    assert_eq!(
+        test.cancel(Some(&mut challenger)),
+        Err(ProgramError::MissingRequiredSignature)
+    );
+    test.set_slot(17);
+    assert_eq!(test.cancel(None), Err(ProgramError::NotEnoughAccountKeys));
+    test.cancel(Some(&mut challenger)).unwrap();
+    test.refund(PollOutcome::OptionA, &mut [&mut voter_1])
+        .unwrap();
+    test.refund(PollOutcome::OptionB, &mut [&mut voter_2])
+        .unwrap();
+    assert_eq!(voter_1.lamports, 100);
+    assert_eq!(voter_2.lamports, 50);
+    assert_eq!(challenger.lamports, MIN_DISPUTE_STAKE);
+    assert_eq!(test.poll.lamports, POLL_RENT);
+    assert_eq!(
+        test.settle(PollOutcome::OptionB, &mut challenger),
+        poll_error(PollError::DisputeNotAllowed)
+    );
+
+    // Polls whose resolver never submits an outcome stall after a window as well
+    let mut test = TestPoll::create_resolved(10, 5);
+    test.creator.is_signer = false;
+    test.set_slot(15);
+    assert_eq!(
+        test.cancel(None),
+        Err(ProgramError::MissingRequiredSignature)
+    );
+    test.set_slot(16);
+    test.cancel(None).unwrap();
+}
+
+#[test]
+pub fn close_unclaimable_poll() {
+    let mut test = TestPoll::create_resolved(10, 1);
+    let mut voter_1 = test.voter();
+    let mut voter_2 = test.voter();
+    test.vote(PollOutcome::OptionA, &mut voter_1, 100).unwrap();
+    test.vote(PollOutcome::OptionA, &mut voter_2, 50).unwrap();
+
+    // Nothing was wagered on the submitted outcome, so every wager is refunded
+    test.set_slot(11);
+    test.resolve(PollOutcome::OptionB).unwrap();
+    test.set_slot(13);
+    assert_eq!(
+        test.refund(PollOutcome::OptionA, &mut [&mut voter_1]),
+        poll_error(PollError::InvalidPayoutList)
+    );
+    assert_eq!(test.close(), poll_error(PollError::PollNotSettled));
+    test.refund(PollOutcome::OptionA, &mut [&mut voter_1, &mut voter_2])
+        .unwrap();
+    test.refund(PollOutcome::OptionB, &mut []).unwrap();
+    test.close().unwrap();
+    assert_eq!(voter_1.lamports, 100);
+    assert_eq!(voter_2.lamports, 50);
+    assert_eq!(test.creator.lamports, POLL_RENT);
+    assert_eq!(test.poll.lamports, 0);
+    let collection = CollectionData::from_bytes(&mut test.collection.data).unwrap();
+    assert!(collection.is_empty());
+}
+
+#[test]
+pub fn add_tally_pages() {
+    let mut test = TestPoll::create(0, 10, 0);
+    let mut page = test.page();
+    assert_eq!(
+        test.add_page(PollOutcome::OptionA, &mut page),
+        poll_error(PollError::TallyNotFull)
+    );
+
+    for _ in 0..TALLY_CAPACITY {
+        let mut voter = test.voter();
+        test.vote(PollOutcome::OptionA, &mut voter, 10).unwrap();
+    }
+    let mut voter = test.voter();
+    assert_eq!(
+        test.vote(PollOutcome::OptionA, &mut voter, 10),
+        poll_error(PollError::MaxTallyCapacity)
+    );
+
+    page.is_signer = false;
+    assert_eq!(
+        test.add_page(PollOutcome::OptionA, &mut page),
+        Err(ProgramError::MissingRequiredSignature)
+    );
+    page.is_signer = true;
+    test.add_page(PollOutcome::OptionA, &mut page).unwrap();
+    let mut other_page = test.page();
+    assert_eq!(
+        test.add_page(PollOutcome::OptionA, &mut other_page),
+        poll_error(PollError::TallyAlreadyExtended)
+    );
+
+    let tally = TallyData::from_bytes(&mut test.tally_a.data).unwrap();
+    assert_eq!(tally.next_tally(), Some(page.key));
+    let next_tally = TallyData::from_bytes(&mut page.data).unwrap();
+    assert_eq!(next_tally.poll_key, test.poll.key);
+    assert_eq!(next_tally.option as u8, PollOutcome::OptionA as u8);
+    assert!(next_tally.is_empty());
+    let poll = PollData::from_bytes(&mut test.poll.data).unwrap();
+    assert_eq!(poll.option_a.page_count.get(), 2);
+
+    // New voters go on the linked page, which must follow the clock in place of
+    // the other tally the harness passes there
+    let mut wager_account = test.wager_account(10);
+    assert_eq!(
+        test.vote(PollOutcome::OptionA, &mut voter, 10),
+        poll_error(PollError::InvalidTallyPage)
+    );
+    process(
+        &test.program_id,
+        &mut [
+            &mut wager_account,
+            &mut test.poll,
+            &mut test.tally_a,
+            &mut voter,
+            &mut test.clock,
+            &mut page,
+        ],
+        &[CommandData::SubmitVote as u8],
+    )
+    .unwrap();
+    let next_tally = TallyData::from_bytes(&mut page.data).unwrap();
+    assert_eq!(next_tally.position(&voter.key), Some(0));
+    assert_eq!(test.quantities(), (170, 0));
+}
+
+#[test]
+pub fn extend_collection() {
+    let mut test = TestPoll::new();
+    test.init_collection().unwrap();
+    let mut next_collection = TestAccount::new(11, &test.program_id, 0, MIN_COLLECTION_SIZE);
+    assert_eq!(
+        test.extend_collection(&mut next_collection),
+        Err(ProgramError::MissingRequiredSignature)
+    );
+    next_collection.is_signer = true;
+    assert_eq!(
+        test.extend_collection(&mut next_collection),
+        poll_error(PollError::CollectionNotFull)
+    );
+
+    test.init_poll(&init_poll_data(10, 0).to_bytes()).unwrap();
+    test.extend_collection(&mut next_collection).unwrap();
+    let collection = CollectionData::from_bytes(&mut test.collection.data).unwrap();
+    assert_eq!(collection.next_collection(), Some(next_collection.key));
+    let next = CollectionData::from_bytes(&mut next_collection.data).unwrap();
+    assert!(next.is_empty());
+
+    assert_eq!(
+        test.extend_collection(&mut next_collection),
+        poll_error(PollError::AccountNotNew)
+    );
+    let mut other_collection =
+        TestAccount::new(12, &test.program_id, 0, MIN_COLLECTION_SIZE).signer();
+    assert_eq!(
+        test.extend_collection(&mut other_collection),
+        poll_error(PollError::CollectionAlreadyExtended)
+    );
+}
+
+/// Collection data as written before layouts were versioned
+fn legacy_collection(data: &mut [u8]) -> Vec<u8> {
+    let size = data.len();
+    let polls = CollectionData::from_bytes(data).unwrap().to_vec();
+    let mut legacy = vec![DataType::Collection as u8];
+    legacy.extend_from_slice(&(polls.len() as u32).to_le_bytes());
+    for poll in polls {
+        legacy.extend_from_slice(poll.as_ref());
+    }
+    legacy.resize(size, 0);
+    legacy
+}
+
+/// Tally data as written before layouts were versioned, with room for `capacity` wagers
+fn legacy_tally(data: &mut [u8], capacity: usize) -> Vec<u8> {
+    let tally = TallyData::from_bytes(data).unwrap();
+    let mut legacy = vec![DataType::Tally as u8];
+    legacy.extend_from_slice(&(tally.len() as u32).to_le_bytes());
+    for (key, wager) in tally.iter() {
+        legacy.extend_from_slice(key.as_ref());
+        legacy.extend_from_slice(&wager.to_le_bytes());
+    }
+    legacy.resize(5 + 40 * capacity, 0);
+    legacy
+}
+
+/// Poll data as written before layouts were versioned, without room to spare
+fn legacy_poll(data: &mut [u8]) -> Vec<u8> {
+    let poll = PollData::from_bytes(data).unwrap();
+    let mut legacy = vec![DataType::Poll as u8];
+    legacy.extend_from_slice(poll.creator_key.as_ref());
+    legacy.extend_from_slice(&poll.last_block.to_le_bytes());
+    legacy.extend_from_slice(&poll.header_len.to_le_bytes());
+    legacy.extend_from_slice(poll.header);
+    for option in &[&poll.option_a, &poll.option_b] {
+        legacy.extend_from_slice(&option.text_len.to_le_bytes());
+        legacy.extend_from_slice(option.text);
+        legacy.extend_from_slice(option.tally_key.as_ref());
+        legacy.extend_from_slice(&option.quantity.get().to_le_bytes());
+    }
+    legacy
+}
+
+#[test]
+pub fn migrate_legacy_accounts() {
+    let mut test = TestPoll::create(0, 10, 0);
+    let mut voter_1 = test.voter();
+    let mut voter_2 = test.voter();
+    test.vote(PollOutcome::OptionA, &mut voter_1, 100).unwrap();
+    test.vote(PollOutcome::OptionA, &mut voter_2, 50).unwrap();
+
+    // Rewrite the accounts as they were stored before layouts were versioned,
+    // leaving the poll and the first tally no room to grow
+    test.collection.data = legacy_collection(&mut test.collection.data);
+    test.poll.data = legacy_poll(&mut test.poll.data);
+    test.tally_a.data = legacy_tally(&mut test.tally_a.data, 2);
+    test.tally_b.data = legacy_tally(&mut test.tally_b.data, TALLY_CAPACITY);
+
+    let mut voter_3 = test.voter();
+    assert_eq!(
+        test.vote(PollOutcome::OptionB, &mut voter_3, 10),
+        poll_error(PollError::UnsupportedVersion)
+    );
+
+    // Tallies are migrated after their poll, which is moved after its collection
+    let migrate = [CommandData::Migrate as u8];
+    assert_eq!(
+        process(
+            &test.program_id,
+            &mut [&mut test.tally_b, &mut test.poll],
+            &migrate
+        ),
+        poll_error(PollError::UnsupportedVersion)
+    );
+    process(&test.program_id, &mut [&mut test.collection], &migrate).unwrap();
+    let collection = CollectionData::from_bytes(&mut test.collection.data).unwrap();
+    assert_eq!(collection.account.version, 2);
+    assert_eq!(collection.to_vec(), vec![test.poll.key]);
+    assert_eq!(
+        process(&test.program_id, &mut [&mut test.collection], &migrate),
+        poll_error(PollError::AccountAlreadyMigrated)
+    );
+
+    let mut new_poll = TestAccount::new(50, &test.program_id, 0, POLL_SIZE).signer();
+    assert_eq!(
+        process(
+            &test.program_id,
+            &mut [&mut test.poll, &mut new_poll],
+            &migrate
+        ),
+        poll_error(PollError::InvalidMigrationLinks)
+    );
+    process(
+        &test.program_id,
+        &mut [&mut test.poll, &mut new_poll, &mut test.collection],
+        &migrate,
+    )
+    .unwrap();
+    assert_eq!(test.poll.lamports, 0);
+    assert!(test.poll.data.iter().all(|&byte| byte == 0));
+    assert_eq!(new_poll.lamports, POLL_RENT + 150);
+    let collection = CollectionData::from_bytes(&mut test.collection.data).unwrap();
+    assert_eq!(collection.to_vec(), vec![new_poll.key]);
+
+    test.poll = new_poll;
+    let poll = PollData::from_bytes(&mut test.poll.data).unwrap();
+    assert_eq!(poll.account.version, 2);
+    assert_eq!(poll.last_block, 10);
+    assert_eq!(poll.header, b"Who wins?");
+    assert_eq!(poll.option_b.text, b"Team B");
+    assert_eq!(test.quantities(), (150, 0));
+
+    process(
+        &test.program_id,
+        &mut [&mut test.tally_b, &mut test.poll],
+        &migrate,
+    )
+    .unwrap();
+    let tally = TallyData::from_bytes(&mut test.tally_b.data).unwrap();
+    assert_eq!(tally.poll_key, test.poll.key);
+    assert_eq!(tally.option as u8, PollOutcome::OptionB as u8);
+    assert!(tally.is_empty());
+
+    let mut new_tally = TestAccount::new(51, &test.program_id, 0, TALLY_SIZE).signer();
+    process(
+        &test.program_id,
+        &mut [&mut test.tally_a, &mut test.poll, &mut new_tally],
+        &migrate,
+    )
+    .unwrap();
+    let tally = TallyData::from_bytes(&mut new_tally.data).unwrap();
+    assert_eq!(tally.option as u8, PollOutcome::OptionA as u8);
+    assert_eq!(
+        tally.iter().collect::<Vec<_>>(),
+        vec![(voter_1.key, 100), (voter_2.key, 50)]
+    );
+    let poll = PollData::from_bytes(&mut test.poll.data).unwrap();
+    assert_eq!(poll.option_a.tally_key, new_tally.key);
+    test.tally_a = new_tally;
+
+    test.set_slot(11);
+    test.claim(PollOutcome::OptionA, &mut [&mut voter_1, &mut voter_2])
+        .unwrap();
+    assert_eq!(voter_1.lamports + voter_2.lamports, 150);
+}
+
+#[test]
+pub fn migrate_full_legacy_collection() {
+    let mut test = TestPoll::create(0, 10, 0);
+
+    // A legacy collection holding 62 polls, the last of which is the test poll
+    let polls: Vec<_> = (0..61)
+        .map(|seed| Pubkey::new(&[seed + 150; 32]))
+        .chain(Some(test.poll.key))
+        .collect();
+    let mut legacy = vec![DataType::Collection as u8];
+    legacy.extend_from_slice(&(polls.len() as u32).to_le_bytes());
+    for poll in &polls {
+        legacy.extend_from_slice(poll.as_ref());
+    }
+    legacy.resize(2000, 0);
+    test.collection.data = legacy;
+    test.poll.data = legacy_poll(&mut test.poll.data);
+
+    let migrate = [CommandData::Migrate as u8];
+    assert_eq!(
+        process(&test.program_id, &mut [&mut test.collection], &migrate),
+        Err(ProgramError::NotEnoughAccountKeys)
+    );
+    let mut next_collection = TestAccount::new(50, &test.program_id, 0, MIN_COLLECTION_SIZE - 1);
+    assert_eq!(
+        process(
+            &test.program_id,
+            &mut [&mut test.collection, &mut next_collection],
+            &migrate
+        ),
+        Err(ProgramError::MissingRequiredSignature)
+    );
+    next_collection.is_signer = true;
+    assert_eq!(
+        process(
+            &test.program_id,
+            &mut [&mut test.collection, &mut next_collection],
+            &migrate
+        ),
+        Err(ProgramError::AccountDataTooSmall)
+    );
+    next_collection.data = vec![0; MIN_COLLECTION_SIZE];
+    process(
+        &test.program_id,
+        &mut [&mut test.collection, &mut next_collection],
+        &migrate,
+    )
+    .unwrap();
+
+    let collection = CollectionData::from_bytes(&mut test.collection.data).unwrap();
+    assert_eq!(collection.to_vec(), polls[..61].to_vec());
+    assert_eq!(collection.next_collection(), Some(next_collection.key));
+    let next = CollectionData::from_bytes(&mut next_collection.data).unwrap();
+    assert_eq!(next.to_vec(), polls[61..].to_vec());
+
+    // Polls on the new page follow it when they move
+    let mut new_poll = TestAccount::new(51, &test.program_id, 0, POLL_SIZE).signer();
+    process(
+        &test.program_id,
+        &mut [&mut test.poll, &mut new_poll, &mut next_collection],
+        &migrate,
+    )
+    .unwrap();
+    let next = CollectionData::from_bytes(&mut next_collection.data).unwrap();
+    assert_eq!(next.to_vec(), vec![new_poll.key]);
+}
+
+#[test]
+pub fn paid_out_legacy_poll_stays() {
+    let mut test = TestPoll::create(0, 10, 0);
+    let mut voter = test.voter();
+    test.vote(PollOutcome::OptionA, &mut voter, 100).unwrap();
+    test.collection.data = legacy_collection(&mut test.collection.data);
+    test.poll.data = legacy_poll(&mut test.poll.data);
+
+    let migrate = [CommandData::Migrate as u8];
+    process(&test.program_id, &mut [&mut test.collection], &migrate).unwrap();
+
+    // Claims used to drain all but one lamport from the poll
+    test.poll.lamports = 1;
+    let mut new_poll = TestAccount::new(50, &test.program_id, POLL_RENT, POLL_SIZE).signer();
+    assert_eq!(
+        process(
+            &test.program_id,
+            &mut [&mut test.poll, &mut new_poll, &mut test.collection],
+            &migrate,
+        ),
+        poll_error(PollError::PollHasNoFunds)
+    );
+    assert_eq!(new_poll.lamports, POLL_RENT);
+    assert!(new_poll.data.iter().all(|&byte| byte == 0));
+}
+
+proptest! {
+    #[test]
+    fn payouts_conserve_lamports(
+        wagers in prop::collection::vec((any::<bool>(), 1..1_000_000_000u64), 1..TALLY_CAPACITY),
+        protocol_fee_bps in 0..1000u16,
+        creator_fee_bps in 0..1000u16,
+    ) {
+        let mut test = TestPoll::create(protocol_fee_bps, 10, creator_fee_bps);
+        let mut voters_a = vec![];
+        let mut voters_b = vec![];
+        for (on_a, wager) in wagers {
+            let mut voter = test.voter();
+            let (option, voters) = if on_a {
+                (PollOutcome::OptionA, &mut voters_a)
+            } else {
+                (PollOutcome::OptionB, &mut voters_b)
+            };
+            // Votes that would tie the poll are rejected
+            if test.vote(option, &mut voter, wager).is_ok() {
+                voters.push((voter, wager));
+            }
+        }
+
+        let (quantity_a, quantity_b) = test.quantities();
+        let (option, winners, losers, winning_quantity) = if quantity_a > quantity_b {
+            (PollOutcome::OptionA, &mut voters_a, &mut voters_b, quantity_a)
+        } else {
+            (PollOutcome::OptionB, &mut voters_b, &mut voters_a, quantity_b)
+        };
+        let pot = quantity_a + quantity_b;
+        let (creator_fee, protocol_fee) = fees(pot, creator_fee_bps, protocol_fee_bps);
+        let winners_pot = pot - creator_fee - protocol_fee;
+
+        test.set_slot(11);
+        let mut winner_accounts: Vec<_> = winners.iter_mut().map(|(voter, _)| voter).collect();
+        test.claim(option, &mut winner_accounts).unwrap();
+
+        // Every lamport wagered ends up with a winner, the creator or the treasury
+        let paid: u64 = winners.iter().map(|(voter, _)| voter.lamports).sum();
+        prop_assert_eq!(paid + test.creator.lamports + test.treasury.lamports, pot);
+        prop_assert_eq!(test.creator.lamports, creator_fee);
+        prop_assert_eq!(test.treasury.lamports, protocol_fee);
+        prop_assert_eq!(test.poll.lamports, POLL_RENT);
+
+        for (voter, wager) in winners.iter() {
+            let floor = u128::from(winners_pot) * u128::from(*wager) / u128::from(winning_quantity);
+            prop_assert!(u128::from(voter.lamports) >= floor);
+        }
+        for (voter, _) in losers.iter() {
+            prop_assert_eq!(voter.lamports, 0);
+        }
+    }
+}
@@ -1,57 +1,86 @@
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
+use prediction_poll_data::POLL_ERROR_MESSAGES;
 use solana_sdk::{
     info,
     program_error::{PrintProgramError, ProgramError},
     program_utils::DecodeError,
 };
-use thiserror::Error;
+use std::fmt;
 
-#[derive(Clone, Debug, Eq, Error, FromPrimitive, PartialEq)]
+/// Custom errors returned by the program, codes index `POLL_ERROR_MESSAGES`
+#[derive(Clone, Debug, Eq, FromPrimitive, PartialEq)]
 pub enum PollError {
-    #[error("todo")]
-    AccountNotNew,
-    #[error("todo")]
-    CannotPayoutToLosers,
-    #[error("todo")]
-    InvalidAccount,
-    #[error("todo")]
-    InvalidDataType,
-    #[error("todo")]
-    InvalidInput,
-    #[error("todo")]
-    InvalidKey,
-    #[error("todo")]
-    InvalidCommand,
-    #[error("todo")]
-    InvalidTallyKey,
-    #[error("todo")]
-    InvalidPayoutList,
-    #[error("todo")]
-    MaxPollCapacity,
-    #[error("todo")]
-    MaxTallyCapacity,
-    #[error("todo")]
-    PollAlreadyCreated,
-    #[error("todo")]
-    PollAlreadyFinished,
-    #[error("todo")]
-    PollNotFinished,
-    #[error("todo")]
-    PollHasNoFunds,
-    #[error("todo")]
-    PollCannotBeEven,
-    #[error("todo")]
-    WagerHasNoFunds,
+    AccountNotNew = 0,
+    CannotPayoutToLosers = 1,
+    InvalidAccount = 2,
+    InvalidDataType = 3,
+    InvalidInput = 4,
+    InvalidKey = 5,
+    InvalidCommand = 6,
+    InvalidTallyKey = 7,
+    InvalidPayoutList = 8,
+    MaxPollCapacity = 9,
+    MaxTallyCapacity = 10,
+    PollAlreadyCreated = 11,
+    PollAlreadyFinished = 12,
+    PollNotFinished = 13,
+    PollHasNoFunds = 14,
+    PollCannotBeEven = 15,
+    WagerHasNoFunds = 16,
+    WagerNotFound = 17,
+    WagerAlreadyClaimed = 18,
+    CollectionAlreadyExtended = 19,
+    CollectionNotFull = 20,
+    PollNotInCollection = 21,
+    PollNotSettled = 22,
+    PollHasNoResolver = 23,
+    PollAlreadyResolved = 24,
+    PollNotResolved = 25,
+    PollNotFinalized = 26,
+    DisputeNotAllowed = 27,
+    PollCancelled = 28,
+    PollNotStarted = 29,
+    HedgingNotAllowed = 30,
+    WagerBelowMinimum = 31,
+    WagerAboveMaximum = 32,
+    VoterCapExceeded = 33,
+    InvalidTallyPage = 34,
+    TallyAlreadyExtended = 35,
+    TallyNotFull = 36,
+    Overflow = 37,
+    UnsupportedVersion = 38,
+    AccountAlreadyMigrated = 39,
+    InvalidMigrationLinks = 40,
+    StakeBelowMinimum = 41,
+    MissingDisputeAuthority = 42,
+    PollHasWinners = 43,
 }
+impl fmt::Display for PollError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(POLL_ERROR_MESSAGES[self.clone() as usize])
+    }
+}
+impl std::error::Error for PollError {}
 impl From<PollError> for ProgramError {
     fn from(e: PollError) -> Self {
         ProgramError::CustomError(e as u32)
     }
 }
+/// Malformed account or instruction data
+impl From<prediction_poll_data::DecodeError> for PollError {
+    fn from(e: prediction_poll_data::DecodeError) -> Self {
+        match e {
+            prediction_poll_data::DecodeError::UnexpectedEnd => PollError::InvalidInput,
+            prediction_poll_data::DecodeError::Overflow => PollError::Overflow,
+            prediction_poll_data::DecodeError::UnsupportedVersion => PollError::UnsupportedVersion,
+            prediction_poll_data::DecodeError::InvalidDataType => PollError::InvalidDataType,
+        }
+    }
+}
 impl<T> DecodeError<T> for PollError {
     fn type_of() -> &'static str {
-        "ProgramError"
+        "PollError"
     }
 }
 impl PrintProgramError for PollError {
@@ -59,24 +88,24 @@ impl PrintProgramError for PollError {
     where
         E: 'static + std::error::Error + DecodeError<E> + PrintProgramError + FromPrimitive,
     {
-        match self {
-            PollError::AccountNotNew => info!("Error: todo"),
-            PollError::CannotPayoutToLosers => info!("Error: todo"),
-            PollError::InvalidAccount => info!("Error: todo"),
-            PollError::InvalidDataType => info!("Error: todo"),
-            PollError::InvalidInput => info!("Error: todo"),
-            PollError::InvalidKey => info!("Error: todo"),
-            PollError::InvalidCommand => info!("Error: todo"),
-            PollError::InvalidTallyKey => info!("Error: todo"),
-            PollError::InvalidPayoutList => info!("Error: todo"),
-            PollError::MaxPollCapacity => info!("Error: todo"),
-            PollError::MaxTallyCapacity => info!("Error: todo"),
-            PollError::PollAlreadyCreated => info!("Error: todo"),
-            PollError::PollAlreadyFinished => info!("Error: todo"),
-            PollError::PollNotFinished => info!("Error: todo"),
-            PollError::PollHasNoFunds => info!("Error: todo"),
-            PollError::PollCannotBeEven => info!("Error: todo"),
-            PollError::WagerHasNoFunds => info!("Error: todo"),
+        info!(&format!("Error: {}", self));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn error_codes() {
+        for (code, message) in POLL_ERROR_MESSAGES.iter().enumerate() {
+            let error = PollError::from_u32(code as u32).unwrap();
+            assert_eq!(error.to_string(), *message);
+            assert_eq!(
+                ProgramError::from(error),
+                ProgramError::CustomError(code as u32)
+            );
         }
+        assert_eq!(PollError::from_u32(POLL_ERROR_MESSAGES.len() as u32), None);
     }
 }
@@ -1,5 +1,5 @@
 use crate::result::PollError;
-use prediction_poll_data::DataType;
+use prediction_poll_data::{AccountHeader, DataType};
 use solana_sdk::{
     account_info::AccountInfo, entrypoint::ProgramResult, program_error::ProgramError,
     pubkey::Pubkey,
@@ -20,10 +20,10 @@ pub fn expect_owned_by(account: &AccountInfo, key: &Pubkey) -> ProgramResult {
 }
 
 pub fn expect_data_type(account: &AccountInfo, data_type: DataType) -> ProgramResult {
-    if DataType::from(account.data.borrow()[0]) as u8 != data_type as u8 {
-        return Err(PollError::InvalidDataType.into());
+    match AccountHeader::from_bytes(&account.data.borrow()) {
+        Ok(header) if header.data_type as u8 == data_type as u8 => Ok(()),
+        _ => Err(PollError::InvalidDataType.into()),
     }
-    Ok(())
 }
 
 pub fn expect_new_account(account: &AccountInfo) -> ProgramResult {
@@ -50,3 +50,41 @@ pub fn expect_gt<T: PartialOrd>(left: T, right: T) -> ProgramResult {
     }
     Ok(())
 }
+
+pub fn expect_lte<T: PartialOrd>(left: T, right: T) -> ProgramResult {
+    if left > right {
+        return Err(PollError::InvalidInput.into());
+    }
+    Ok(())
+}
+
+/// Adds lamports to an account balance, failing rather than wrapping
+pub fn credit(account: &AccountInfo, lamports: u64) -> ProgramResult {
+    let balance = account
+        .lamports()
+        .checked_add(lamports)
+        .ok_or(PollError::Overflow)?;
+    **account.lamports.borrow_mut() = balance;
+    Ok(())
+}
+
+/// Removes lamports from an account balance, failing rather than wrapping
+pub fn debit(account: &AccountInfo, lamports: u64) -> ProgramResult {
+    let balance = account
+        .lamports()
+        .checked_sub(lamports)
+        .ok_or(PollError::Overflow)?;
+    **account.lamports.borrow_mut() = balance;
+    Ok(())
+}
+
+/// Drains an account into `destination` and clears its data so it can't be reused
+pub fn close_account(account: &AccountInfo, destination: &AccountInfo) -> ProgramResult {
+    let lamports = account.lamports();
+    credit(destination, lamports)?;
+    **account.lamports.borrow_mut() = 0;
+    for byte in account.data.borrow_mut().iter_mut() {
+        *byte = 0;
+    }
+    Ok(())
+}
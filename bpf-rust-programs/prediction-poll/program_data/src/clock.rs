@@ -1,11 +1,22 @@
+use crate::decode::{split_at, DecodeError};
+
+/// Contents of the clock sysvar account
 pub struct ClockData {
     pub slot: u64,
+    pub epoch: u64,
+    pub leader_schedule_epoch: u64,
+    pub unix_timestamp: i64,
 }
 
 impl ClockData {
-    pub fn from_bytes(data: &[u8]) -> Self {
-        Self {
+    pub fn from_bytes(data: &[u8]) -> Result<Self, DecodeError> {
+        let (data, _) = split_at(data, 40)?;
+        // The second field of the sysvar is unused
+        Ok(Self {
             slot: u64::from_le_bytes(*array_ref!(data, 0, 8)),
-        }
+            epoch: u64::from_le_bytes(*array_ref!(data, 16, 8)),
+            leader_schedule_epoch: u64::from_le_bytes(*array_ref!(data, 24, 8)),
+            unix_timestamp: i64::from_le_bytes(*array_ref!(data, 32, 8)),
+        })
     }
 }
@@ -1,70 +1,132 @@
-use crate::DataType;
-use alloc::slice::from_raw_parts_mut;
-#[cfg(any(test, feature = "wasm"))]
+use crate::decode::DecodeError;
+use crate::{AccountHeader, DataType, Decode, Encode, LeU32, Versioned};
 use alloc::vec::Vec;
+use prediction_poll_data_derive::Layout;
 use solana_sdk::pubkey::Pubkey;
 
 type PubkeyData = [u8; 32];
 
 /// Min data size for a poll collection
-/// Breakdown: data_type (1) + poll_count (4) + one poll (32)
-pub const MIN_COLLECTION_SIZE: usize = 1 + 4 + 32;
+/// Breakdown: data_type (1) + version (1) + next_collection (32) + poll_count (4) + one poll (32)
+pub const MIN_COLLECTION_SIZE: usize = 1 + 1 + 32 + 4 + 32;
 
+#[derive(Layout)]
+#[layout(version = 2)]
 pub struct CollectionData<'a> {
-    pub data_type: DataType,
-    poll_count: &'a mut u32,
-    polls: &'a mut [PubkeyData],
+    pub account: AccountHeader,
+    next_collection: &'a mut PubkeyData,
+    poll_count: LeU32<'a>,
+    #[layout(chunks = 32)]
+    polls: &'a mut [u8],
 }
 
 impl<'a> CollectionData<'a> {
-    pub fn from_bytes(data: &'a mut [u8]) -> Self {
-        let (data_type, data) = data.split_at_mut(1);
-        let (poll_count, data) = data.split_at_mut(4);
-        #[allow(clippy::cast_ptr_alignment)]
-        let poll_count = unsafe { &mut *(&mut poll_count[0] as *mut u8 as *mut u32) };
-        Self {
-            data_type: DataType::from(data_type[0]),
-            poll_count,
-            polls: unsafe {
-                from_raw_parts_mut(&mut data[0] as *mut u8 as *mut _, data.len() / 32)
-            },
-        }
+    /// Writes the header of an empty collection
+    pub fn copy_to_bytes(dst: &'a mut [u8]) -> Result<(), DecodeError> {
+        let mut poll_count = 0u32.to_le_bytes();
+        let collection = CollectionData {
+            account: AccountHeader::new(DataType::Collection, Self::VERSION),
+            next_collection: &mut [0; 32],
+            poll_count: LeU32::new(&mut poll_count),
+            polls: &mut [],
+        };
+        collection.encode(dst)?;
+        Ok(())
+    }
+
+    pub fn from_bytes(data: &'a mut [u8]) -> Result<Self, DecodeError> {
+        AccountHeader::from_bytes(data)?.expect_version(Self::VERSION)?;
+        let (collection, _) = Self::decode(data)?;
+        Ok(collection)
     }
 }
 
 impl CollectionData<'_> {
     pub fn contains(&self, poll: &Pubkey) -> bool {
-        let poll_key_data = array_ref!(poll.as_ref(), 0, 32);
-        for i in 0..self.len() {
-            if self.polls[i] == *poll_key_data {
-                return true;
-            }
+        self.position(poll).is_some()
+    }
+
+    fn poll(&self, index: usize) -> &PubkeyData {
+        array_ref!(self.polls, index * 32, 32)
+    }
+
+    fn poll_mut(&mut self, index: usize) -> &mut PubkeyData {
+        array_mut_ref!(self.polls, index * 32, 32)
+    }
+
+    fn position(&self, poll: &Pubkey) -> Option<usize> {
+        (0..self.len()).find(|&index| self.poll(index) == poll.as_ref())
+    }
+
+    /// Collections are paged, a full collection links to the page holding newer polls
+    pub fn next_collection(&self) -> Option<Pubkey> {
+        if *self.next_collection == [0; 32] {
+            None
+        } else {
+            Some(Pubkey::new(&self.next_collection[..]))
         }
-        false
+    }
+
+    pub fn set_next_collection(&mut self, collection: &Pubkey) {
+        self.next_collection.copy_from_slice(collection.as_ref());
     }
 
     pub fn capacity(&self) -> usize {
-        self.polls.len()
+        self.polls.len() / 32
     }
 
     pub fn is_empty(&self) -> bool {
         self.len() == 0
     }
 
+    /// Stored counts past the capacity are clamped so corrupt data can't index out of bounds
     pub fn len(&self) -> usize {
-        *self.poll_count as usize
+        (self.poll_count.get() as usize).min(self.capacity())
     }
 
     pub fn add_poll(&mut self, poll: &Pubkey) {
-        self.polls[self.len()].copy_from_slice(poll.as_ref());
-        *self.poll_count += 1;
+        let index = self.len();
+        self.poll_mut(index).copy_from_slice(poll.as_ref());
+        self.poll_count.set(self.poll_count.get() + 1);
+    }
+
+    /// Removes a poll by moving the last poll into its slot
+    pub fn remove_poll(&mut self, poll: &Pubkey) -> bool {
+        match self.position(poll) {
+            Some(index) => {
+                let last = self.len() - 1;
+                let last_poll = *self.poll(last);
+                *self.poll_mut(index) = last_poll;
+                *self.poll_mut(last) = [0; 32];
+                self.poll_count.set(self.poll_count.get() - 1);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Points the links to an account at its new key, returning how many changed
+    pub fn relink(&mut self, old: &Pubkey, new: &Pubkey) -> usize {
+        let mut links = 0;
+        if let Some(index) = self.position(old) {
+            self.poll_mut(index).copy_from_slice(new.as_ref());
+            links += 1;
+        }
+        if self.next_collection() == Some(*old) {
+            self.set_next_collection(new);
+            links += 1;
+        }
+        links
+    }
+
+    /// Bytes holding the header and stored polls
+    pub fn used_len(&self) -> usize {
+        self.encoded_len() - self.polls.len() + self.len() * 32
     }
 
-    #[cfg(any(test, feature = "wasm"))]
     pub fn to_vec(&self) -> Vec<Pubkey> {
-        self.polls[..self.len()]
-            .iter()
-            .map(|poll| Pubkey::new(&poll[..]))
+        (0..self.len())
+            .map(|index| Pubkey::new(&self.poll(index)[..]))
             .collect()
     }
 }
@@ -77,7 +139,8 @@ mod test {
     pub fn add_poll() {
         let poll_key = Pubkey::new(&[0; 32]);
         let mut data = vec![0; MIN_COLLECTION_SIZE];
-        let mut collection = CollectionData::from_bytes(&mut data[..]);
+        CollectionData::copy_to_bytes(&mut data[..]).unwrap();
+        let mut collection = CollectionData::from_bytes(&mut data[..]).unwrap();
 
         assert_eq!(collection.len(), 0);
         assert_eq!(collection.capacity(), 1);
@@ -91,4 +154,59 @@ mod test {
         assert!(collection.contains(&poll_key));
         assert_eq!(collection.to_vec()[0], poll_key);
     }
+
+    #[test]
+    pub fn remove_poll() {
+        let poll_keys = [
+            Pubkey::new(&[1; 32]),
+            Pubkey::new(&[2; 32]),
+            Pubkey::new(&[3; 32]),
+        ];
+        let mut data = vec![0; MIN_COLLECTION_SIZE + 2 * 32];
+        CollectionData::copy_to_bytes(&mut data[..]).unwrap();
+        let mut collection = CollectionData::from_bytes(&mut data[..]).unwrap();
+        for poll_key in poll_keys.iter() {
+            collection.add_poll(poll_key);
+        }
+
+        assert!(!collection.remove_poll(&Pubkey::new(&[4; 32])));
+        assert!(collection.remove_poll(&poll_keys[0]));
+
+        assert_eq!(collection.len(), 2);
+        assert!(!collection.contains(&poll_keys[0]));
+        assert_eq!(collection.to_vec(), vec![poll_keys[2], poll_keys[1]]);
+
+        collection.add_poll(&poll_keys[0]);
+        assert_eq!(collection.len(), 3);
+        assert!(collection.contains(&poll_keys[0]));
+    }
+
+    #[test]
+    pub fn init_collection() {
+        let mut data = vec![0xff; MIN_COLLECTION_SIZE];
+        CollectionData::copy_to_bytes(&mut data[..]).unwrap();
+        let collection = CollectionData::from_bytes(&mut data[..]).unwrap();
+
+        assert_eq!(
+            collection.account,
+            AccountHeader::new(DataType::Collection, 2)
+        );
+        assert!(collection.is_empty());
+        assert_eq!(collection.next_collection(), None);
+    }
+
+    #[test]
+    pub fn next_collection() {
+        let next_key = Pubkey::new(&[2; 32]);
+        let mut data = vec![0; MIN_COLLECTION_SIZE];
+        CollectionData::copy_to_bytes(&mut data[..]).unwrap();
+        let mut collection = CollectionData::from_bytes(&mut data[..]).unwrap();
+
+        assert_eq!(collection.next_collection(), None);
+        collection.set_next_collection(&next_key);
+        assert_eq!(collection.next_collection(), Some(next_key));
+
+        let collection = CollectionData::from_bytes(&mut data[..]).unwrap();
+        assert_eq!(collection.next_collection(), Some(next_key));
+    }
 }
@@ -6,6 +6,16 @@ pub enum CommandData {
     InitPoll,
     SubmitVote,
     SubmitClaim,
+    ClaimPayout,
+    ExtendCollection,
+    ClosePoll,
+    ResolvePoll,
+    ChallengeOutcome,
+    SettleDispute,
+    CancelPoll,
+    AddTallyPage,
+    Migrate,
+    RefundWagers,
 }
 
 impl TryFrom<u8> for CommandData {
@@ -17,6 +27,16 @@ impl TryFrom<u8> for CommandData {
             1 => Ok(CommandData::InitPoll),
             2 => Ok(CommandData::SubmitVote),
             3 => Ok(CommandData::SubmitClaim),
+            4 => Ok(CommandData::ClaimPayout),
+            5 => Ok(CommandData::ExtendCollection),
+            6 => Ok(CommandData::ClosePoll),
+            7 => Ok(CommandData::ResolvePoll),
+            8 => Ok(CommandData::ChallengeOutcome),
+            9 => Ok(CommandData::SettleDispute),
+            10 => Ok(CommandData::CancelPoll),
+            11 => Ok(CommandData::AddTallyPage),
+            12 => Ok(CommandData::Migrate),
+            13 => Ok(CommandData::RefundWagers),
             _ => Err(()),
         }
     }
@@ -0,0 +1,46 @@
+/// Errors returned when account or instruction data can't be decoded
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DecodeError {
+    /// Data ended before a field or a length-prefixed value could be read
+    UnexpectedEnd,
+    /// A value derived from the data does not fit in its field
+    Overflow,
+    /// Account data was written with a layout version this decoder does not read
+    UnsupportedVersion,
+    /// Account data type has no layout
+    InvalidDataType,
+}
+
+pub(crate) fn split_at(data: &[u8], mid: usize) -> Result<(&[u8], &[u8]), DecodeError> {
+    if mid > data.len() {
+        return Err(DecodeError::UnexpectedEnd);
+    }
+    Ok(data.split_at(mid))
+}
+
+pub(crate) fn split_at_mut(
+    data: &mut [u8],
+    mid: usize,
+) -> Result<(&mut [u8], &mut [u8]), DecodeError> {
+    if mid > data.len() {
+        return Err(DecodeError::UnexpectedEnd);
+    }
+    Ok(data.split_at_mut(mid))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn split() {
+        let mut data = [1, 2, 3];
+        assert_eq!(split_at(&data, 3), Ok((&[1, 2, 3][..], &[][..])));
+        assert_eq!(split_at(&data, 4), Err(DecodeError::UnexpectedEnd));
+        assert!(split_at_mut(&mut data, 1).is_ok());
+        assert_eq!(
+            split_at_mut(&mut data, usize::MAX).err(),
+            Some(DecodeError::UnexpectedEnd)
+        );
+    }
+}
@@ -0,0 +1,53 @@
+/// Messages for the custom error codes returned by the prediction poll program,
+/// indexed by code. Codes are stable, new errors must be appended.
+pub const POLL_ERROR_MESSAGES: [&str; 44] = [
+    "Account is already initialized",         // 0
+    "Tally is not for the winning option",    // 1
+    "Account is not owned by the program",    // 2
+    "Account holds the wrong type of data",   // 3
+    "Instruction data is invalid",            // 4
+    "Account key does not match",             // 5
+    "Unknown command",                        // 6
+    "Tally does not belong to the poll",      // 7
+    "Payout accounts do not match the tally", // 8
+    "Collection is full",                     // 9
+    "Tally is full",                          // 10
+    "Poll is already in the collection",      // 11
+    "Poll has already finished",              // 12
+    "Poll has not finished yet",              // 13
+    "Poll does not have enough funds",        // 14
+    "Wager would tie the poll",               // 15
+    "Wager account has no funds",             // 16
+    "No wager found for the account",         // 17
+    "Wager has already been claimed",         // 18
+    "Collection has already been extended",   // 19
+    "Collection is not full",                 // 20
+    "Poll is not in the collection",          // 21
+    "Poll still has unclaimed wagers",        // 22
+    "Poll has no resolver",                   // 23
+    "Poll outcome has already been resolved", // 24
+    "Poll outcome has not been resolved",     // 25
+    "Poll outcome is not final yet",          // 26
+    "Poll outcome can no longer be disputed", // 27
+    "Poll has been cancelled",                // 28
+    "Poll has not started yet",               // 29
+    "Voters may only wager on one option",    // 30
+    "Wager is below the poll minimum",        // 31
+    "Wager is above the poll maximum",        // 32
+    "Wager exceeds the voter cap",            // 33
+    "Tally pages are not linked in order",    // 34
+    "Tally has already been extended",        // 35
+    "Tally is not full",                      // 36
+    "Arithmetic overflow",                    // 37
+    "Account layout version is unsupported",  // 38
+    "Account is already migrated",            // 39
+    "Migrated account links do not match",    // 40
+    "Stake is below the dispute minimum",     // 41
+    "Resolver needs a dispute authority",     // 42
+    "Poll has winners to pay out",            // 43
+];
+
+/// Message for a custom error code returned by the prediction poll program
+pub fn poll_error_message(code: u32) -> Option<&'static str> {
+    POLL_ERROR_MESSAGES.get(code as usize).copied()
+}
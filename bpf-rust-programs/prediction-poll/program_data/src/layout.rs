@@ -0,0 +1,327 @@
+//! Field encodings used by `#[derive(Layout)]`
+//!
+//! Values are little-endian and packed without padding. Layouts decode from
+//! shared bytes like instruction data or from mutable account data, where the
+//! zero-copy fields can be updated in place.
+
+use crate::decode::{split_at, split_at_mut, DecodeError};
+use crate::{AccountHeader, DataType, PollOutcome, VERSIONED_FLAG};
+use alloc::vec::Vec;
+use core::mem::size_of;
+use solana_sdk::pubkey::Pubkey;
+
+/// Data a layout is decoded from
+pub trait Bytes<'a>: Sized {
+    fn len(&self) -> usize;
+    fn split_at(self, mid: usize) -> Result<(Self, Self), DecodeError>;
+    fn into_slice(self) -> &'a [u8];
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<'a> Bytes<'a> for &'a [u8] {
+    fn len(&self) -> usize {
+        <[u8]>::len(self)
+    }
+
+    fn split_at(self, mid: usize) -> Result<(Self, Self), DecodeError> {
+        split_at(self, mid)
+    }
+
+    fn into_slice(self) -> &'a [u8] {
+        self
+    }
+}
+
+impl<'a> Bytes<'a> for &'a mut [u8] {
+    fn len(&self) -> usize {
+        <[u8]>::len(self)
+    }
+
+    fn split_at(self, mid: usize) -> Result<(Self, Self), DecodeError> {
+        split_at_mut(self, mid)
+    }
+
+    fn into_slice(self) -> &'a [u8] {
+        self
+    }
+}
+
+/// Reads a value from the front of the data and returns it with the rest
+pub trait Decode<'a, B: Bytes<'a>>: Sized {
+    fn decode(data: B) -> Result<(Self, B), DecodeError>;
+}
+
+/// Writes a value to the front of a buffer and returns the rest
+pub trait Encode {
+    fn encoded_len(&self) -> usize;
+    fn encode<'b>(&self, dst: &'b mut [u8]) -> Result<&'b mut [u8], DecodeError>;
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = alloc::vec![0; self.encoded_len()];
+        self.encode(&mut bytes).unwrap();
+        bytes
+    }
+}
+
+/// Version of a layout, bumped whenever its encoding changes
+pub trait Versioned {
+    const VERSION: u8;
+}
+
+fn put<'b>(dst: &'b mut [u8], bytes: &[u8]) -> Result<&'b mut [u8], DecodeError> {
+    let (dst, rest) = split_at_mut(dst, bytes.len())?;
+    dst.copy_from_slice(bytes);
+    Ok(rest)
+}
+
+macro_rules! le_int {
+    ($($int:ty),*) => {$(
+        impl<'a, B: Bytes<'a>> Decode<'a, B> for $int {
+            fn decode(data: B) -> Result<(Self, B), DecodeError> {
+                let (value, data) = data.split_at(size_of::<$int>())?;
+                let value = array_ref!(value.into_slice(), 0, size_of::<$int>());
+                Ok((<$int>::from_le_bytes(*value), data))
+            }
+        }
+
+        impl Encode for $int {
+            fn encoded_len(&self) -> usize {
+                size_of::<$int>()
+            }
+
+            fn encode<'b>(&self, dst: &'b mut [u8]) -> Result<&'b mut [u8], DecodeError> {
+                put(dst, &self.to_le_bytes())
+            }
+        }
+    )*};
+}
+
+le_int!(u8, u16, u32, u64, i64);
+
+/// Single byte values read with `From<u8>`, so unknown bytes decode to an invalid variant
+macro_rules! byte_value {
+    ($($value:ty),*) => {$(
+        impl<'a, B: Bytes<'a>> Decode<'a, B> for $value {
+            fn decode(data: B) -> Result<(Self, B), DecodeError> {
+                let (value, data) = u8::decode(data)?;
+                Ok((<$value>::from(value), data))
+            }
+        }
+
+        impl Encode for $value {
+            fn encoded_len(&self) -> usize {
+                1
+            }
+
+            fn encode<'b>(&self, dst: &'b mut [u8]) -> Result<&'b mut [u8], DecodeError> {
+                put(dst, &[*self as u8])
+            }
+        }
+    )*};
+}
+
+byte_value!(DataType, PollOutcome);
+
+/// Unversioned accounts start with a lone data type byte and decode as version 1
+impl<'a, B: Bytes<'a>> Decode<'a, B> for AccountHeader {
+    fn decode(data: B) -> Result<(Self, B), DecodeError> {
+        let (value, data) = u8::decode(data)?;
+        if value & VERSIONED_FLAG == 0 {
+            return Ok((AccountHeader::new(DataType::from(value), 1), data));
+        }
+
+        let (version, data) = u8::decode(data)?;
+        if version < 2 {
+            return Err(DecodeError::UnsupportedVersion);
+        }
+        let data_type = match value & !VERSIONED_FLAG {
+            0 => DataType::Invalid,
+            value => DataType::from(value),
+        };
+        Ok((AccountHeader::new(data_type, version), data))
+    }
+}
+
+impl Encode for AccountHeader {
+    fn encoded_len(&self) -> usize {
+        if self.version > 1 {
+            2
+        } else {
+            1
+        }
+    }
+
+    fn encode<'b>(&self, dst: &'b mut [u8]) -> Result<&'b mut [u8], DecodeError> {
+        if self.version > 1 {
+            put(dst, &[self.data_type as u8 | VERSIONED_FLAG, self.version])
+        } else {
+            put(dst, &[self.data_type as u8])
+        }
+    }
+}
+
+/// Flags are set by any nonzero byte
+impl<'a, B: Bytes<'a>> Decode<'a, B> for bool {
+    fn decode(data: B) -> Result<(Self, B), DecodeError> {
+        let (value, data) = u8::decode(data)?;
+        Ok((value != 0, data))
+    }
+}
+
+impl Encode for bool {
+    fn encoded_len(&self) -> usize {
+        1
+    }
+
+    fn encode<'b>(&self, dst: &'b mut [u8]) -> Result<&'b mut [u8], DecodeError> {
+        put(dst, &[*self as u8])
+    }
+}
+
+impl<'a, B: Bytes<'a>> Decode<'a, B> for Pubkey {
+    fn decode(data: B) -> Result<(Self, B), DecodeError> {
+        let (key, data) = data.split_at(32)?;
+        Ok((Pubkey::new(key.into_slice()), data))
+    }
+}
+
+impl Encode for Pubkey {
+    fn encoded_len(&self) -> usize {
+        32
+    }
+
+    fn encode<'b>(&self, dst: &'b mut [u8]) -> Result<&'b mut [u8], DecodeError> {
+        put(dst, self.as_ref())
+    }
+}
+
+/// Byte slices take all of the data, layouts give them an extent with `len` or `chunks`
+impl<'a, B: Bytes<'a>> Decode<'a, B> for &'a [u8] {
+    fn decode(data: B) -> Result<(Self, B), DecodeError> {
+        let len = data.len();
+        let (value, data) = data.split_at(len)?;
+        Ok((value.into_slice(), data))
+    }
+}
+
+impl Encode for &[u8] {
+    fn encoded_len(&self) -> usize {
+        self.len()
+    }
+
+    fn encode<'b>(&self, dst: &'b mut [u8]) -> Result<&'b mut [u8], DecodeError> {
+        put(dst, self)
+    }
+}
+
+impl<'a> Decode<'a, &'a mut [u8]> for &'a mut [u8] {
+    fn decode(data: &'a mut [u8]) -> Result<(Self, &'a mut [u8]), DecodeError> {
+        let len = data.len();
+        split_at_mut(data, len)
+    }
+}
+
+impl Encode for &mut [u8] {
+    fn encoded_len(&self) -> usize {
+        self.len()
+    }
+
+    fn encode<'b>(&self, dst: &'b mut [u8]) -> Result<&'b mut [u8], DecodeError> {
+        put(dst, self)
+    }
+}
+
+impl<'a> Decode<'a, &'a mut [u8]> for &'a mut u8 {
+    fn decode(data: &'a mut [u8]) -> Result<(Self, &'a mut [u8]), DecodeError> {
+        let (value, data) = split_at_mut(data, 1)?;
+        Ok((&mut value[0], data))
+    }
+}
+
+impl Encode for &mut u8 {
+    fn encoded_len(&self) -> usize {
+        1
+    }
+
+    fn encode<'b>(&self, dst: &'b mut [u8]) -> Result<&'b mut [u8], DecodeError> {
+        put(dst, &[**self])
+    }
+}
+
+impl<'a> Decode<'a, &'a mut [u8]> for &'a mut [u8; 32] {
+    fn decode(data: &'a mut [u8]) -> Result<(Self, &'a mut [u8]), DecodeError> {
+        let (value, data) = split_at_mut(data, 32)?;
+        Ok((array_mut_ref!(value, 0, 32), data))
+    }
+}
+
+impl Encode for &mut [u8; 32] {
+    fn encoded_len(&self) -> usize {
+        32
+    }
+
+    fn encode<'b>(&self, dst: &'b mut [u8]) -> Result<&'b mut [u8], DecodeError> {
+        put(dst, &self[..])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn fields() {
+        let mut bytes = [0; 2 + 8 + 1 + 32];
+        let key = Pubkey::new(&[7; 32]);
+        let dst = 513u16.encode(&mut bytes).unwrap();
+        let dst = (-2i64).encode(dst).unwrap();
+        let dst = true.encode(dst).unwrap();
+        let dst = key.encode(dst).unwrap();
+        assert!(dst.is_empty());
+        assert_eq!(1u8.encode(dst), Err(DecodeError::UnexpectedEnd));
+
+        let data = &bytes[..];
+        let (value, data) = u16::decode(data).unwrap();
+        assert_eq!(value, 513);
+        let (value, data) = i64::decode(data).unwrap();
+        assert_eq!(value, -2);
+        let (value, data) = bool::decode(data).unwrap();
+        assert!(value);
+        let (value, data) = Pubkey::decode(data).unwrap();
+        assert_eq!(value, key);
+        assert_eq!(u8::decode(data), Err(DecodeError::UnexpectedEnd));
+    }
+
+    #[test]
+    pub fn account_header() {
+        let legacy = AccountHeader::from_bytes(&[DataType::Poll as u8, 0xff]).unwrap();
+        assert_eq!(legacy, AccountHeader::new(DataType::Poll, 1));
+        assert_eq!(legacy.to_bytes(), [DataType::Poll as u8]);
+
+        let header = AccountHeader::new(DataType::Tally, 2);
+        let bytes = header.to_bytes();
+        assert_eq!(bytes, [DataType::Tally as u8 | VERSIONED_FLAG, 2]);
+        assert_eq!(AccountHeader::from_bytes(&bytes), Ok(header));
+        assert_eq!(
+            header.expect_version(1),
+            Err(DecodeError::UnsupportedVersion)
+        );
+        assert_eq!(header.expect_version(2), Ok(()));
+        assert_eq!(
+            legacy.expect_version(2),
+            Err(DecodeError::UnsupportedVersion)
+        );
+
+        assert_eq!(
+            AccountHeader::from_bytes(&[VERSIONED_FLAG | 1, 1]),
+            Err(DecodeError::UnsupportedVersion)
+        );
+        assert_eq!(
+            AccountHeader::from_bytes(&[VERSIONED_FLAG]),
+            Err(DecodeError::UnexpectedEnd)
+        );
+    }
+}
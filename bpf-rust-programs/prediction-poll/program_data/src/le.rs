@@ -0,0 +1,69 @@
+use crate::decode::{split_at_mut, DecodeError};
+use crate::{Decode, Encode};
+
+/// Defines a little-endian integer field that is read and written in place,
+/// so account data can stay zero-copy without unaligned references
+macro_rules! le_field {
+    ($name:ident, $int:ty, $size:expr) => {
+        #[cfg_attr(test, derive(PartialEq, Debug))]
+        pub struct $name<'a>(&'a mut [u8; $size]);
+
+        impl<'a> $name<'a> {
+            pub fn new(bytes: &'a mut [u8; $size]) -> Self {
+                Self(bytes)
+            }
+
+            pub fn get(&self) -> $int {
+                <$int>::from_le_bytes(*self.0)
+            }
+
+            pub fn set(&mut self, value: $int) {
+                *self.0 = value.to_le_bytes();
+            }
+        }
+
+        impl<'a> Decode<'a, &'a mut [u8]> for $name<'a> {
+            fn decode(data: &'a mut [u8]) -> Result<(Self, &'a mut [u8]), DecodeError> {
+                let (value, data) = split_at_mut(data, $size)?;
+                Ok((Self::new(array_mut_ref!(value, 0, $size)), data))
+            }
+        }
+
+        impl Encode for $name<'_> {
+            fn encoded_len(&self) -> usize {
+                $size
+            }
+
+            fn encode<'b>(&self, dst: &'b mut [u8]) -> Result<&'b mut [u8], DecodeError> {
+                self.get().encode(dst)
+            }
+        }
+    };
+}
+
+le_field!(LeU32, u32, 4);
+le_field!(LeU64, u64, 8);
+le_field!(LeI64, i64, 8);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn unaligned() {
+        let mut data = [0u8; 13];
+        let (_, data) = data.split_at_mut(1);
+        let (count, quantity) = data.split_at_mut(4);
+        let mut count = LeU32::new(array_mut_ref!(count, 0, 4));
+        let mut quantity = LeU64::new(array_mut_ref!(quantity, 0, 8));
+
+        count.set(3);
+        quantity.set(u64::MAX - 1);
+        assert_eq!(count.get(), 3);
+        assert_eq!(quantity.get(), u64::MAX - 1);
+
+        count.set(count.get() + 1);
+        assert_eq!(count.get(), 4);
+        assert_eq!(quantity.get(), u64::MAX - 1);
+    }
+}
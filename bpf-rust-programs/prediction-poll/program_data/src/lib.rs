@@ -10,13 +10,25 @@ extern crate wasm_bindgen;
 mod clock;
 mod collection;
 mod command;
+mod decode;
+mod error;
+mod layout;
+mod le;
+mod migrate;
 mod poll;
+mod quote;
 mod tally;
 
 pub use clock::*;
 pub use collection::*;
 pub use command::*;
+pub use decode::DecodeError;
+pub use error::*;
+pub use layout::{Bytes, Decode, Encode, Versioned};
+pub use le::{LeI64, LeU32, LeU64};
+pub use migrate::*;
 pub use poll::*;
+pub use quote::*;
 pub use tally::*;
 
 #[repr(u8)]
@@ -41,3 +53,33 @@ impl From<u8> for DataType {
         }
     }
 }
+
+/// Set on the data type byte of accounts that store their layout version in the
+/// following byte. Accounts written before layouts were versioned leave it clear.
+pub const VERSIONED_FLAG: u8 = 0x80;
+
+/// Leading bytes of every account, the data type and the layout version it was written with
+#[derive(Copy, Clone)]
+#[cfg_attr(test, derive(PartialEq, Debug))]
+pub struct AccountHeader {
+    pub data_type: DataType,
+    pub version: u8,
+}
+
+impl AccountHeader {
+    pub fn new(data_type: DataType, version: u8) -> Self {
+        Self { data_type, version }
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, DecodeError> {
+        Self::decode(data).map(|(header, _)| header)
+    }
+
+    /// Layouts only decode their latest version, older accounts are migrated first
+    pub fn expect_version(&self, latest: u8) -> Result<(), DecodeError> {
+        if self.version != latest {
+            return Err(DecodeError::UnsupportedVersion);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,522 @@
+//! Upgrades account data written with an older layout version
+//!
+//! Version 1 is the layout accounts were written with before layouts were
+//! versioned. Its fields are converted to the latest layout and everything the
+//! old layout didn't record starts out unset: collections have no next page,
+//! polls have no limits, fees or resolver and wagers are unclaimed. Polls paid
+//! out before they were migrated kept a single lamport, they can only be
+//! migrated in place where there is no balance left to pay them again.
+//!
+//! Tallies didn't record the poll and option they belong to, those are taken
+//! from their poll with `Migration::set_poll`.
+
+use crate::decode::{split_at_mut, DecodeError};
+use crate::{
+    AccountHeader, CollectionData, DataType, Decode, DisputeState, Encode, LeI64, LeU32, LeU64,
+    PollData, PollDisputeData, PollOptionData, PollOutcome, TallyData, Versioned,
+    MIN_COLLECTION_SIZE, MIN_TALLY_SIZE,
+};
+use alloc::vec::Vec;
+use prediction_poll_data_derive::Layout;
+use solana_sdk::pubkey::Pubkey;
+
+/// Collection layout before layouts were versioned, without a next page
+#[derive(Layout)]
+#[layout(version = 1)]
+struct CollectionV1<'a> {
+    account: AccountHeader,
+    poll_count: u32,
+    #[layout(chunks = 32)]
+    polls: &'a [u8],
+}
+
+/// Tally layout before layouts were versioned, entries are a key and a wager
+#[derive(Layout)]
+#[layout(version = 1)]
+struct TallyV1<'a> {
+    account: AccountHeader,
+    tally_count: u32,
+    #[layout(chunks = 40)]
+    tallies: &'a [u8],
+}
+
+/// Poll layout before layouts were versioned, the most wagered option won
+#[derive(Layout)]
+#[layout(version = 1)]
+struct PollV1<'a> {
+    account: AccountHeader,
+    creator_key: Pubkey,
+    last_block: u64,
+    header_len: u32,
+    #[layout(len = header_len)]
+    header: &'a [u8],
+    option_a: PollOptionV1<'a>,
+    option_b: PollOptionV1<'a>,
+}
+
+#[derive(Layout)]
+#[layout(version = 1)]
+struct PollOptionV1<'a> {
+    text_len: u32,
+    #[layout(len = text_len)]
+    text: &'a [u8],
+    tally_key: Pubkey,
+    quantity: u64,
+}
+
+/// Upgrade of an account from the version it was written with to its latest layout
+#[cfg_attr(test, derive(PartialEq, Debug))]
+pub struct Migration {
+    pub from: AccountHeader,
+    pub to: AccountHeader,
+    migrated: Vec<u8>, // used bytes of the account at the latest version
+    needs_poll: bool,
+}
+
+impl Migration {
+    pub fn new(data: &mut [u8]) -> Result<Self, DecodeError> {
+        let from = AccountHeader::from_bytes(data)?;
+        let (migrated, needs_poll) = if from.version == 1 {
+            upgrade_v1(from.data_type, data)?
+        } else {
+            (used_bytes(from.data_type, data)?, false)
+        };
+
+        Ok(Self {
+            from,
+            to: AccountHeader::from_bytes(&migrated)?,
+            migrated,
+            needs_poll,
+        })
+    }
+
+    pub fn is_needed(&self) -> bool {
+        self.from.version < self.to.version
+    }
+
+    /// Legacy tallies have to be told which poll and option they belong to
+    pub fn needs_poll(&self) -> bool {
+        self.needs_poll
+    }
+
+    pub fn set_poll(&mut self, poll_key: &Pubkey, option: PollOutcome) -> Result<(), DecodeError> {
+        let mut copy = self.migrated.clone();
+        let mut tally = TallyData::from_bytes(&mut copy)?;
+        tally.poll_key = *poll_key;
+        tally.option = option;
+        tally.encode(&mut self.migrated)?;
+        self.needs_poll = false;
+        Ok(())
+    }
+
+    /// Legacy collections are never linked to, so instead of moving the polls
+    /// that don't fit in `len` bytes move to a new page linked from this one.
+    /// Returns the data of the new page.
+    pub fn split_collection(
+        &mut self,
+        len: usize,
+        next_collection: &Pubkey,
+    ) -> Result<Vec<u8>, DecodeError> {
+        let mut copy = self.migrated.clone();
+        let collection = CollectionData::from_bytes(&mut copy)?;
+        let capacity = len
+            .checked_sub(MIN_COLLECTION_SIZE - 32)
+            .ok_or(DecodeError::UnexpectedEnd)?
+            / 32;
+        let polls = collection.to_vec();
+        let (kept, moved) = polls.split_at(capacity.min(polls.len()));
+
+        self.migrated = collection_page(kept)?;
+        CollectionData::from_bytes(&mut self.migrated)?.set_next_collection(next_collection);
+        collection_page(moved)
+    }
+
+    /// Account data size needed at the latest version
+    pub fn migrated_len(&self) -> usize {
+        self.migrated.len()
+    }
+
+    /// Upgrades the data where it is, the free space after it is cleared
+    pub fn apply(&self, data: &mut [u8]) -> Result<(), DecodeError> {
+        let (dst, rest) = split_at_mut(data, self.migrated.len())?;
+        dst.copy_from_slice(&self.migrated);
+        for byte in rest.iter_mut() {
+            *byte = 0;
+        }
+        Ok(())
+    }
+
+    /// Writes the upgraded data to the front of another account
+    pub fn apply_to(&self, dst: &mut [u8]) -> Result<(), DecodeError> {
+        let (dst, _) = split_at_mut(dst, self.migrated.len())?;
+        dst.copy_from_slice(&self.migrated);
+        Ok(())
+    }
+}
+
+/// Bytes of an account at the latest version holding data
+fn used_bytes(data_type: DataType, data: &mut [u8]) -> Result<Vec<u8>, DecodeError> {
+    let used_len = match data_type {
+        DataType::Collection => CollectionData::from_bytes(data)?.used_len(),
+        DataType::Poll => PollData::from_bytes(data)?.encoded_len(),
+        DataType::Tally => TallyData::from_bytes(data)?.used_len(),
+        _ => return Err(DecodeError::InvalidDataType),
+    };
+    Ok(data[..used_len].to_vec())
+}
+
+/// Converts a legacy account and returns whether it still needs its poll
+fn upgrade_v1(data_type: DataType, data: &[u8]) -> Result<(Vec<u8>, bool), DecodeError> {
+    match data_type {
+        DataType::Collection => Ok((upgrade_collection(data)?, false)),
+        DataType::Poll => Ok((upgrade_poll(data)?, false)),
+        DataType::Tally => Ok((upgrade_tally(data)?, true)),
+        _ => Err(DecodeError::InvalidDataType),
+    }
+}
+
+fn upgrade_collection(data: &[u8]) -> Result<Vec<u8>, DecodeError> {
+    let (legacy, _) = CollectionV1::decode(data)?;
+    let polls: Vec<_> = legacy
+        .polls
+        .chunks(32)
+        .take(legacy.poll_count as usize)
+        .map(Pubkey::new)
+        .collect();
+    collection_page(&polls)
+}
+
+/// Collection holding exactly the given polls
+fn collection_page(polls: &[Pubkey]) -> Result<Vec<u8>, DecodeError> {
+    let mut data = alloc::vec![0; MIN_COLLECTION_SIZE - 32 + polls.len() * 32];
+    CollectionData::copy_to_bytes(&mut data)?;
+    let mut collection = CollectionData::from_bytes(&mut data)?;
+    for poll in polls {
+        collection.add_poll(poll);
+    }
+    Ok(data)
+}
+
+fn upgrade_tally(data: &[u8]) -> Result<Vec<u8>, DecodeError> {
+    let (legacy, _) = TallyV1::decode(data)?;
+    let tallies = legacy.tallies.chunks(40).take(legacy.tally_count as usize);
+
+    let mut migrated = alloc::vec![0; MIN_TALLY_SIZE - 41 + tallies.len() * 41];
+    TallyData::copy_to_bytes(&mut migrated, &Pubkey::default(), PollOutcome::Unresolved)?;
+    let mut tally = TallyData::from_bytes(&mut migrated)?;
+    for entry in tallies {
+        let wager = u64::from_le_bytes(*array_ref!(entry, 32, 8));
+        tally.add_tally(&Pubkey::new(&entry[..32]), wager);
+    }
+    Ok(migrated)
+}
+
+fn upgrade_poll(data: &[u8]) -> Result<Vec<u8>, DecodeError> {
+    let (legacy, _) = PollV1::decode(data)?;
+
+    let mut stake = 0u64.to_le_bytes();
+    let mut updated_slot = 0u64.to_le_bytes();
+    let mut updated_time = 0i64.to_le_bytes();
+    let mut quantity_a = legacy.option_a.quantity.to_le_bytes();
+    let mut quantity_b = legacy.option_b.quantity.to_le_bytes();
+    let mut page_count_a = 1u32.to_le_bytes();
+    let mut page_count_b = 1u32.to_le_bytes();
+    let poll = PollData {
+        account: AccountHeader::new(DataType::Poll, PollData::VERSION),
+        creator_key: legacy.creator_key,
+        first_block: 0,
+        last_block: legacy.last_block,
+        end_time: 0,
+        no_hedging: false,
+        min_wager: 0,
+        max_wager: 0,
+        voter_cap: 0,
+        creator_fee_bps: 0,
+        protocol_fee_bps: 0,
+        resolver_key: Pubkey::default(),
+        outcome: &mut (PollOutcome::Unresolved as u8),
+        dispute: PollDisputeData {
+            window: 0,
+            authority_key: Pubkey::default(),
+            state: &mut (DisputeState::Unchallenged as u8),
+            challenger_key: &mut [0; 32],
+            stake: LeU64::new(&mut stake),
+            updated_slot: LeU64::new(&mut updated_slot),
+            updated_time: LeI64::new(&mut updated_time),
+        },
+        header_len: legacy.header_len,
+        header: legacy.header,
+        option_a: PollOptionData {
+            text_len: legacy.option_a.text_len,
+            text: legacy.option_a.text,
+            tally_key: legacy.option_a.tally_key,
+            quantity: LeU64::new(&mut quantity_a),
+            page_count: LeU32::new(&mut page_count_a),
+        },
+        option_b: PollOptionData {
+            text_len: legacy.option_b.text_len,
+            text: legacy.option_b.text,
+            tally_key: legacy.option_b.tally_key,
+            quantity: LeU64::new(&mut quantity_b),
+            page_count: LeU32::new(&mut page_count_b),
+        },
+    };
+    Ok(poll.to_bytes())
+}
+
+/// Points the links an account holds to `old` at `new` and returns how many changed.
+/// Links held by value are written back by encoding the layout over its own data.
+pub fn relink(data: &mut [u8], old: &Pubkey, new: &Pubkey) -> Result<usize, DecodeError> {
+    let mut copy: Vec<u8> = data.to_vec();
+    match AccountHeader::from_bytes(data)?.data_type {
+        DataType::Collection => {
+            let mut collection = CollectionData::from_bytes(&mut copy)?;
+            let links = collection.relink(old, new);
+            collection.encode(data)?;
+            Ok(links)
+        }
+        DataType::Poll => {
+            let mut poll = PollData::from_bytes(&mut copy)?;
+            let links = poll.relink(old, new);
+            poll.encode(data)?;
+            Ok(links)
+        }
+        DataType::Tally => {
+            let mut tally = TallyData::from_bytes(&mut copy)?;
+            let links = tally.relink(old, new);
+            tally.encode(data)?;
+            Ok(links)
+        }
+        _ => Err(DecodeError::InvalidDataType),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Collection data as written before layouts were versioned
+    fn legacy_collection(size: usize, polls: &[Pubkey]) -> Vec<u8> {
+        let mut data = vec![DataType::Collection as u8];
+        data.extend_from_slice(&(polls.len() as u32).to_le_bytes());
+        for poll in polls {
+            data.extend_from_slice(poll.as_ref());
+        }
+        data.resize(size, 0);
+        data
+    }
+
+    /// Tally data as written before layouts were versioned
+    fn legacy_tally(size: usize, wagers: usize) -> Vec<u8> {
+        let mut data = vec![DataType::Tally as u8];
+        data.extend_from_slice(&(wagers as u32).to_le_bytes());
+        for wager in 0..wagers {
+            data.extend_from_slice(&[wager as u8 + 10; 32]);
+            data.extend_from_slice(&(wager as u64 + 1).to_le_bytes());
+        }
+        data.resize(size, 0);
+        data
+    }
+
+    /// Poll data as written before layouts were versioned
+    fn legacy_poll(size: usize) -> Vec<u8> {
+        let mut data = vec![DataType::Poll as u8];
+        data.extend_from_slice(&[1; 32]);
+        data.extend_from_slice(&999u64.to_le_bytes());
+        data.extend_from_slice(&4u32.to_le_bytes());
+        data.extend_from_slice(b"poll");
+        for (text, tally_key, quantity) in [(&b"yes"[..], 2, 100u64), (&b"no"[..], 3, 50)].iter() {
+            data.extend_from_slice(&(text.len() as u32).to_le_bytes());
+            data.extend_from_slice(text);
+            data.extend_from_slice(&[*tally_key; 32]);
+            data.extend_from_slice(&quantity.to_le_bytes());
+        }
+        data.resize(size, 0);
+        data
+    }
+
+    #[test]
+    pub fn legacy_decode() {
+        let mut data = legacy_tally(MIN_TALLY_SIZE + 41, 2);
+        assert_eq!(
+            AccountHeader::from_bytes(&data),
+            Ok(AccountHeader::new(DataType::Tally, 1))
+        );
+        assert_eq!(
+            TallyData::from_bytes(&mut data).err(),
+            Some(DecodeError::UnsupportedVersion)
+        );
+        assert_eq!(
+            PollData::from_bytes(&mut legacy_poll(200)).err(),
+            Some(DecodeError::UnsupportedVersion)
+        );
+        assert_eq!(
+            CollectionData::from_bytes(&mut legacy_collection(100, &[])).err(),
+            Some(DecodeError::UnsupportedVersion)
+        );
+
+        data[0] |= crate::VERSIONED_FLAG;
+        data[1] = TallyData::VERSION + 1;
+        assert_eq!(
+            Migration::new(&mut data).err(),
+            Some(DecodeError::UnsupportedVersion)
+        );
+    }
+
+    #[test]
+    pub fn migrate_tally() {
+        let poll_key = Pubkey::new(&[1; 32]);
+        let mut data = legacy_tally(MIN_TALLY_SIZE + 41, 2);
+        let mut migration = Migration::new(&mut data).unwrap();
+        assert!(migration.is_needed());
+        assert!(migration.needs_poll());
+        assert_eq!(migration.from, AccountHeader::new(DataType::Tally, 1));
+        assert_eq!(migration.to, AccountHeader::new(DataType::Tally, 2));
+        assert_eq!(migration.migrated_len(), MIN_TALLY_SIZE + 41);
+
+        migration.set_poll(&poll_key, PollOutcome::OptionB).unwrap();
+        assert!(!migration.needs_poll());
+        migration.apply(&mut data).unwrap();
+        let tally = TallyData::from_bytes(&mut data).unwrap();
+        assert_eq!(tally.account, AccountHeader::new(DataType::Tally, 2));
+        assert_eq!(tally.poll_key, poll_key);
+        assert_eq!(tally.option, PollOutcome::OptionB);
+        assert_eq!(tally.next_tally(), None);
+        assert_eq!(
+            tally.iter().collect::<Vec<_>>(),
+            vec![(Pubkey::new(&[10; 32]), 1), (Pubkey::new(&[11; 32]), 2)]
+        );
+        assert!(!tally.is_claimed(0) && !tally.is_claimed(1));
+        assert_eq!(tally.capacity(), 2);
+
+        assert!(!Migration::new(&mut data).unwrap().is_needed());
+    }
+
+    #[test]
+    pub fn migrate_collection() {
+        let polls = [Pubkey::new(&[1; 32]), Pubkey::new(&[2; 32])];
+        let mut data = legacy_collection(MIN_COLLECTION_SIZE + 32, &polls);
+        let migration = Migration::new(&mut data).unwrap();
+        assert!(!migration.needs_poll());
+        assert_eq!(migration.migrated_len(), MIN_COLLECTION_SIZE + 32);
+
+        migration.apply(&mut data).unwrap();
+        let collection = CollectionData::from_bytes(&mut data).unwrap();
+        assert_eq!(collection.account.version, 2);
+        assert_eq!(collection.to_vec(), polls.to_vec());
+        assert_eq!(collection.next_collection(), None);
+        assert_eq!(collection.capacity(), 2);
+    }
+
+    #[test]
+    pub fn split_collection() {
+        let polls: Vec<_> = (1..=4).map(|seed| Pubkey::new(&[seed; 32])).collect();
+        let mut data = legacy_collection(5 + 4 * 32, &polls);
+        let mut migration = Migration::new(&mut data).unwrap();
+        assert!(migration.migrated_len() > data.len());
+
+        let next_key = Pubkey::new(&[9; 32]);
+        let mut next_data = migration.split_collection(data.len(), &next_key).unwrap();
+        assert!(migration.migrated_len() <= data.len());
+        migration.apply(&mut data).unwrap();
+        let collection = CollectionData::from_bytes(&mut data).unwrap();
+        assert_eq!(collection.to_vec(), polls[..2].to_vec());
+        assert_eq!(collection.next_collection(), Some(next_key));
+
+        let next = CollectionData::from_bytes(&mut next_data).unwrap();
+        assert_eq!(next.to_vec(), polls[2..].to_vec());
+        assert_eq!(next.next_collection(), None);
+
+        let mut data = legacy_collection(MIN_COLLECTION_SIZE - 33, &[]);
+        let mut migration = Migration::new(&mut data).unwrap();
+        assert_eq!(
+            migration.split_collection(data.len(), &next_key).err(),
+            Some(DecodeError::UnexpectedEnd)
+        );
+    }
+
+    #[test]
+    pub fn migrate_poll() {
+        let mut data = legacy_poll(400);
+        let migration = Migration::new(&mut data).unwrap();
+        assert!(!migration.needs_poll());
+        migration.apply(&mut data).unwrap();
+
+        let poll = PollData::from_bytes(&mut data).unwrap();
+        assert_eq!(poll.account, AccountHeader::new(DataType::Poll, 2));
+        assert_eq!(poll.creator_key, Pubkey::new(&[1; 32]));
+        assert_eq!(poll.first_block, 0);
+        assert_eq!(poll.last_block, 999);
+        assert!(!poll.has_end_time() && !poll.has_resolver() && !poll.no_hedging);
+        assert_eq!(poll.creator_fee_bps + poll.protocol_fee_bps, 0);
+        assert_eq!(PollOutcome::from(*poll.outcome), PollOutcome::Unresolved);
+        assert_eq!(poll.header, b"poll");
+        assert_eq!(poll.option_a.text, b"yes");
+        assert_eq!(poll.option_a.tally_key, Pubkey::new(&[2; 32]));
+        assert_eq!(poll.option_a.quantity.get(), 100);
+        assert_eq!(poll.option_a.page_count.get(), 1);
+        assert_eq!(poll.option_b.text, b"no");
+        assert_eq!(poll.option_b.tally_key, Pubkey::new(&[3; 32]));
+        assert_eq!(poll.option_b.quantity.get(), 50);
+        assert_eq!(poll.option_b.page_count.get(), 1);
+        assert_eq!(poll.encoded_len(), migration.migrated_len());
+    }
+
+    #[test]
+    pub fn migrate_to_new_account() {
+        let mut data = legacy_tally(MIN_TALLY_SIZE - 1, 1);
+        let mut migration = Migration::new(&mut data).unwrap();
+        migration
+            .set_poll(&Pubkey::new(&[1; 32]), PollOutcome::OptionA)
+            .unwrap();
+        assert_eq!(migration.migrated_len(), MIN_TALLY_SIZE);
+        assert_eq!(migration.apply(&mut data), Err(DecodeError::UnexpectedEnd));
+
+        let mut new_data = vec![0; MIN_TALLY_SIZE];
+        migration.apply_to(&mut new_data).unwrap();
+        let tally = TallyData::from_bytes(&mut new_data).unwrap();
+        assert_eq!(tally.account.version, 2);
+        assert_eq!(tally.poll_key, Pubkey::new(&[1; 32]));
+        assert_eq!(tally.key(0), Pubkey::new(&[10; 32]));
+        assert_eq!(tally.wager(0), 1);
+    }
+
+    #[test]
+    pub fn relink_accounts() {
+        let old = Pubkey::new(&[1; 32]);
+        let new = Pubkey::new(&[2; 32]);
+
+        let mut data = vec![0; MIN_TALLY_SIZE];
+        TallyData::copy_to_bytes(&mut data, &old, PollOutcome::OptionA).unwrap();
+        TallyData::from_bytes(&mut data)
+            .unwrap()
+            .set_next_tally(&old);
+        assert_eq!(relink(&mut data, &old, &new), Ok(2));
+        let tally = TallyData::from_bytes(&mut data).unwrap();
+        assert_eq!(tally.poll_key, new);
+        assert_eq!(tally.next_tally(), Some(new));
+
+        let mut data = vec![0; MIN_COLLECTION_SIZE + 32];
+        CollectionData::copy_to_bytes(&mut data).unwrap();
+        let mut collection = CollectionData::from_bytes(&mut data).unwrap();
+        collection.add_poll(&Pubkey::new(&[3; 32]));
+        collection.add_poll(&old);
+        assert_eq!(relink(&mut data, &old, &new), Ok(1));
+        assert_eq!(relink(&mut data, &old, &new), Ok(0));
+        let collection = CollectionData::from_bytes(&mut data).unwrap();
+        assert_eq!(collection.to_vec(), vec![Pubkey::new(&[3; 32]), new]);
+
+        assert_eq!(
+            relink(
+                &mut legacy_collection(MIN_COLLECTION_SIZE, &[old]),
+                &old,
+                &new
+            ),
+            Err(DecodeError::UnsupportedVersion)
+        );
+        assert_eq!(
+            relink(&mut [0; 8], &old, &new),
+            Err(DecodeError::InvalidDataType)
+        );
+    }
+}
@@ -1,41 +1,87 @@
-use super::InitPollData;
-use crate::DataType;
-#[cfg(test)]
-use alloc::vec::Vec;
+use super::{DisputeState, InitPollData, PollDisputeData};
+use crate::decode::DecodeError;
+use crate::{
+    AccountHeader, DataType, Decode, Encode, LeI64, LeU32, LeU64, Versioned, PROTOCOL_FEE_BPS,
+};
+use prediction_poll_data_derive::Layout;
 use solana_sdk::pubkey::Pubkey;
 
+#[repr(u8)]
+#[derive(Copy, Clone, PartialEq)]
+#[cfg_attr(test, derive(Debug))]
+pub enum PollOutcome {
+    Unresolved,
+    OptionA,
+    OptionB,
+    Cancelled,
+    Invalid,
+}
+
+impl From<u8> for PollOutcome {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => PollOutcome::Unresolved,
+            1 => PollOutcome::OptionA,
+            2 => PollOutcome::OptionB,
+            3 => PollOutcome::Cancelled,
+            _ => PollOutcome::Invalid,
+        }
+    }
+}
+
+#[derive(Layout)]
 #[cfg_attr(test, derive(PartialEq, Debug))]
+#[layout(version = 2)]
 pub struct PollData<'a> {
-    pub data_type: DataType,
+    pub account: AccountHeader,
     pub creator_key: Pubkey,
+    pub first_block: u64,
     pub last_block: u64,
+    pub end_time: i64, // unix timestamp, zero when the poll ends at the last block
+    pub no_hedging: bool,
+    pub min_wager: u64, // zero for no limit
+    pub max_wager: u64, // zero for no limit
+    pub voter_cap: u64, // zero for no limit
+    pub creator_fee_bps: u16,
+    pub protocol_fee_bps: u16, // PROTOCOL_FEE_BPS when the poll was created
+    pub resolver_key: Pubkey,  // default key when the most wagered option wins
+    pub outcome: &'a mut u8,
+    pub dispute: PollDisputeData<'a>,
     pub header_len: u32,
+    #[layout(len = header_len)]
     pub header: &'a [u8],
     pub option_a: PollOptionData<'a>,
     pub option_b: PollOptionData<'a>,
 }
 
-impl<'a> PollData<'a> {
-    #[cfg(test)]
-    pub fn length(&self) -> usize {
-        (1 + 32 + 8 + 4 + self.header_len) as usize
-            + self.option_a.length()
-            + self.option_b.length()
+impl PollData<'_> {
+    pub fn has_resolver(&self) -> bool {
+        self.resolver_key != Pubkey::default()
     }
 
-    #[cfg(test)]
-    pub fn to_bytes(&self) -> Vec<u8> {
-        let mut bytes = Vec::with_capacity(self.length());
-        bytes.push(self.data_type as u8);
-        bytes.extend_from_slice(self.creator_key.as_ref());
-        bytes.extend_from_slice(&self.last_block.to_le_bytes());
-        bytes.extend_from_slice(&self.header_len.to_le_bytes());
-        bytes.extend_from_slice(self.header);
-        bytes.extend(self.option_a.to_bytes().into_iter());
-        bytes.extend(self.option_b.to_bytes().into_iter());
-        bytes
+    pub fn has_end_time(&self) -> bool {
+        self.end_time != 0
     }
 
+    pub fn is_cancelled(&self) -> bool {
+        PollOutcome::from(*self.outcome) == PollOutcome::Cancelled
+    }
+
+    /// Points the links to an account at its new key, returning how many changed
+    pub fn relink(&mut self, old: &Pubkey, new: &Pubkey) -> usize {
+        let mut links = 0;
+        for option in [&mut self.option_a, &mut self.option_b].iter_mut() {
+            if option.tally_key == *old {
+                option.tally_key = *new;
+                links += 1;
+            }
+        }
+        links
+    }
+}
+
+impl<'a> PollData<'a> {
+    #[allow(clippy::too_many_arguments)]
     pub fn copy_to_bytes(
         dst: &'a mut [u8],
         init: InitPollData<'a>,
@@ -43,121 +89,101 @@ impl<'a> PollData<'a> {
         tally_a_key: &'a Pubkey,
         tally_b_key: &'a Pubkey,
         slot: u64,
-    ) {
-        let (data_type, dst) = dst.split_at_mut(1);
-        data_type[0] = DataType::Poll as u8;
-
-        let (dst_creator_key, dst) = dst.split_at_mut(32);
-        dst_creator_key.copy_from_slice(creator_key.as_ref());
-
-        let last_block = slot + u64::from(init.timeout);
-        let (dst_last_block, dst) = dst.split_at_mut(8);
-        dst_last_block.copy_from_slice(&last_block.to_le_bytes());
-
-        let (header_len, dst) = dst.split_at_mut(4);
-        header_len.copy_from_slice(&init.header_len.to_le_bytes());
-        let (header, dst) = dst.split_at_mut(init.header_len as usize);
-        header.copy_from_slice(&init.header);
-
-        let dst = PollOptionData::copy_to_bytes(dst, init.option_a, tally_a_key, 0);
-        PollOptionData::copy_to_bytes(dst, init.option_b, tally_b_key, 0);
+    ) -> Result<(), DecodeError> {
+        // Polls scheduled in the past open immediately
+        let first_block = slot.max(init.start_slot);
+        let last_block = first_block
+            .checked_add(u64::from(init.timeout))
+            .ok_or(DecodeError::Overflow)?;
+
+        let mut stake = 0u64.to_le_bytes();
+        let mut updated_slot = 0u64.to_le_bytes();
+        let mut updated_time = 0i64.to_le_bytes();
+        let mut quantity_a = 0u64.to_le_bytes();
+        let mut quantity_b = 0u64.to_le_bytes();
+        let mut page_count_a = 1u32.to_le_bytes();
+        let mut page_count_b = 1u32.to_le_bytes();
+        let poll = PollData {
+            account: AccountHeader::new(DataType::Poll, Self::VERSION),
+            creator_key: *creator_key,
+            first_block,
+            last_block,
+            end_time: init.end_time,
+            no_hedging: init.no_hedging,
+            min_wager: init.min_wager,
+            max_wager: init.max_wager,
+            voter_cap: init.voter_cap,
+            creator_fee_bps: init.creator_fee_bps,
+            protocol_fee_bps: PROTOCOL_FEE_BPS,
+            resolver_key: init.resolver_key,
+            outcome: &mut (PollOutcome::Unresolved as u8),
+            dispute: PollDisputeData {
+                window: init.dispute_window,
+                authority_key: init.authority_key,
+                state: &mut (DisputeState::Unchallenged as u8),
+                challenger_key: &mut [0; 32],
+                stake: LeU64::new(&mut stake),
+                updated_slot: LeU64::new(&mut updated_slot),
+                updated_time: LeI64::new(&mut updated_time),
+            },
+            header_len: init.header_len,
+            header: init.header,
+            option_a: PollOptionData {
+                text_len: init.option_a_len,
+                text: init.option_a,
+                tally_key: *tally_a_key,
+                quantity: LeU64::new(&mut quantity_a),
+                page_count: LeU32::new(&mut page_count_a),
+            },
+            option_b: PollOptionData {
+                text_len: init.option_b_len,
+                text: init.option_b,
+                tally_key: *tally_b_key,
+                quantity: LeU64::new(&mut quantity_b),
+                page_count: LeU32::new(&mut page_count_b),
+            },
+        };
+        poll.encode(dst)?;
+        Ok(())
     }
 
-    pub fn from_bytes(data: &'a mut [u8]) -> Self {
-        let (data_type, data) = data.split_at_mut(1);
-        let data_type = DataType::from(data_type[0]);
-
-        let (creator_key, data) = data.split_at_mut(32);
-        let creator_key = Pubkey::new(creator_key);
-
-        let (last_block, data) = data.split_at_mut(8);
-        let last_block = u64::from_le_bytes(*array_ref!(last_block, 0, 8));
-
-        let (header_len, data) = data.split_at_mut(4);
-        let header_len = u32::from_le_bytes(*array_ref!(header_len, 0, 4));
-        let (header, data) = data.split_at_mut(header_len as usize);
-
-        let (option_a, data) = PollOptionData::from_bytes(data);
-        let (option_b, _) = PollOptionData::from_bytes(data);
-
-        Self {
-            data_type,
-            creator_key,
-            last_block,
-            header_len,
-            header,
-            option_a,
-            option_b,
-        }
+    pub fn from_bytes(data: &'a mut [u8]) -> Result<Self, DecodeError> {
+        AccountHeader::from_bytes(data)?.expect_version(Self::VERSION)?;
+        let (poll, _) = Self::decode(data)?;
+        Ok(poll)
     }
 }
 
+#[derive(Layout)]
 #[cfg_attr(test, derive(PartialEq, Debug))]
+#[layout(version = 1)]
 pub struct PollOptionData<'a> {
     pub text_len: u32,
+    #[layout(len = text_len)]
     pub text: &'a [u8],
     pub tally_key: Pubkey,
-    pub quantity: &'a mut u64,
+    pub quantity: LeU64<'a>,
+    pub page_count: LeU32<'a>, // tally accounts linked from the tally key
 }
 
 impl<'a> PollOptionData<'a> {
-    #[cfg(test)]
-    pub fn length(&self) -> usize {
-        (4 + self.text_len + 32 + 8) as usize
-    }
-
-    #[cfg(test)]
-    pub fn to_bytes(&self) -> Vec<u8> {
-        let mut bytes = Vec::with_capacity(self.length());
-        bytes.extend_from_slice(&self.text_len.to_le_bytes());
-        bytes.extend_from_slice(self.text);
-        bytes.extend_from_slice(self.tally_key.as_ref());
-        bytes.extend_from_slice(&self.quantity.to_le_bytes());
-        bytes
-    }
-
+    /// Writes an option with a single tally page and returns the rest of the buffer
     pub fn copy_to_bytes(
         dst: &'a mut [u8],
         text: &'a [u8],
         tally_key: &'a Pubkey,
         quantity: u64,
-    ) -> &'a mut [u8] {
-        let text_len = text.len() as u32;
-        let (dst_text_len, dst) = dst.split_at_mut(4);
-        dst_text_len.copy_from_slice(&text_len.to_le_bytes());
-
-        let (dst_text, dst) = dst.split_at_mut(text.len());
-        dst_text.copy_from_slice(text);
-
-        let (dst_tally_key, dst) = dst.split_at_mut(32);
-        dst_tally_key.copy_from_slice(tally_key.as_ref());
-
-        let (dst_quantity, dst) = dst.split_at_mut(8);
-        dst_quantity.copy_from_slice(&quantity.to_le_bytes());
-        dst
-    }
-
-    pub fn from_bytes(data: &'a mut [u8]) -> (Self, &'a mut [u8]) {
-        let (text_len, data) = data.split_at_mut(4);
-        let text_len = u32::from_le_bytes(*array_ref!(text_len, 0, 4));
-        let (text, data) = data.split_at_mut(text_len as usize);
-
-        let (tally_key, data) = data.split_at_mut(32);
-        let tally_key = Pubkey::new(tally_key);
-
-        let (quantity, data) = data.split_at_mut(8);
-        #[allow(clippy::cast_ptr_alignment)]
-        let quantity = unsafe { &mut *(&mut quantity[0] as *mut u8 as *mut u64) };
-
-        (
-            Self {
-                text_len,
-                text,
-                tally_key,
-                quantity,
-            },
-            data,
-        )
+    ) -> Result<&'a mut [u8], DecodeError> {
+        let mut quantity = quantity.to_le_bytes();
+        let mut page_count = 1u32.to_le_bytes();
+        let option = PollOptionData {
+            text_len: text.len() as u32,
+            text,
+            tally_key: *tally_key,
+            quantity: LeU64::new(&mut quantity),
+            page_count: LeU32::new(&mut page_count),
+        };
+        option.encode(dst)
     }
 }
 
@@ -171,54 +197,131 @@ mod test {
         let header = "poll".as_bytes();
         let option_a = "first option".as_bytes();
         let option_a_key = Pubkey::new(&[1; 32]);
-        let mut quantity_a = 100;
+        let mut quantity_a = 100u64.to_le_bytes();
         let option_b = "second option".as_bytes();
         let option_b_key = Pubkey::new(&[2; 32]);
-        let mut quantity_b = 101;
+        let mut quantity_b = 101u64.to_le_bytes();
+        let mut page_count_a = 1u32.to_le_bytes();
+        let mut page_count_b = 3u32.to_le_bytes();
+        let mut outcome = PollOutcome::OptionB as u8;
+        let mut dispute_state = 0;
+        let mut challenger_key = [5; 32];
+        let mut stake = [0; 8];
+        let mut updated_slot = [0; 8];
+        let mut updated_time = [0; 8];
 
         let data = PollData {
-            data_type: DataType::Poll,
+            account: AccountHeader::new(DataType::Poll, 2),
             creator_key,
+            first_block: 900,
             last_block: 999,
+            end_time: 1_600_000_000,
+            no_hedging: false,
+            min_wager: 10,
+            max_wager: 0,
+            voter_cap: 500,
+            creator_fee_bps: 100,
+            protocol_fee_bps: 50,
+            resolver_key: Pubkey::new(&[4; 32]),
+            outcome: &mut outcome,
+            dispute: PollDisputeData {
+                window: 20,
+                authority_key: Pubkey::new(&[6; 32]),
+                state: &mut dispute_state,
+                challenger_key: &mut challenger_key,
+                stake: LeU64::new(&mut stake),
+                updated_slot: LeU64::new(&mut updated_slot),
+                updated_time: LeI64::new(&mut updated_time),
+            },
             header_len: header.len() as u32,
             header,
             option_a: PollOptionData {
                 text_len: option_a.len() as u32,
                 text: option_a,
                 tally_key: option_a_key,
-                quantity: &mut quantity_a,
+                quantity: LeU64::new(&mut quantity_a),
+                page_count: LeU32::new(&mut page_count_a),
             },
             option_b: PollOptionData {
                 text_len: option_b.len() as u32,
                 text: option_b,
                 tally_key: option_b_key,
-                quantity: &mut quantity_b,
+                quantity: LeU64::new(&mut quantity_b),
+                page_count: LeU32::new(&mut page_count_b),
             },
         };
 
         let mut bytes = data.to_bytes();
-        let data_copy = PollData::from_bytes(&mut bytes[..]);
+        let data_copy = PollData::from_bytes(&mut bytes[..]).unwrap();
 
         assert_eq!(data, data_copy);
-        assert_eq!(data.length(), bytes.len());
+        assert_eq!(data.encoded_len(), bytes.len());
     }
 
     #[test]
     pub fn option_serialization() {
         let key = Pubkey::new(&[0; 32]);
         let text = "option text".as_bytes();
-        let mut quantity = 100;
+        let mut quantity = 100u64.to_le_bytes();
+        let mut page_count = 2u32.to_le_bytes();
         let data = PollOptionData {
             text_len: text.len() as u32,
             text,
             tally_key: key,
-            quantity: &mut quantity,
+            quantity: LeU64::new(&mut quantity),
+            page_count: LeU32::new(&mut page_count),
         };
 
         let mut bytes = data.to_bytes();
-        let (data_copy, _) = PollOptionData::from_bytes(&mut bytes[..]);
+        let (data_copy, _) = PollOptionData::decode(&mut bytes[..]).unwrap();
 
         assert_eq!(data, data_copy);
-        assert_eq!(data.length(), bytes.len());
+        assert_eq!(data.encoded_len(), bytes.len());
+    }
+
+    #[test]
+    pub fn unaligned_option() {
+        let mut bytes = [0; 1 + 4 + 3 + 32 + 8 + 4];
+        let key = Pubkey::new(&[1; 32]);
+        PollOptionData::copy_to_bytes(&mut bytes[1..], b"odd", &key, 7).unwrap();
+
+        let (mut option, _) = PollOptionData::decode(&mut bytes[1..]).unwrap();
+        option.quantity.set(option.quantity.get() + 5);
+        option.page_count.set(option.page_count.get() + 1);
+
+        let (option, _) = PollOptionData::decode(&mut bytes[1..]).unwrap();
+        assert_eq!(option.text, b"odd");
+        assert_eq!(option.quantity.get(), 12);
+        assert_eq!(option.page_count.get(), 2);
+        assert_eq!(bytes[40..48], 12u64.to_le_bytes());
+    }
+
+    #[test]
+    pub fn timeout_overflow() {
+        let key = Pubkey::new(&[1; 32]);
+        let init = InitPollData {
+            timeout: u32::MAX,
+            start_slot: 0,
+            end_time: 0,
+            no_hedging: false,
+            min_wager: 0,
+            max_wager: 0,
+            voter_cap: 0,
+            creator_fee_bps: 0,
+            resolver_key: Pubkey::default(),
+            dispute_window: 0,
+            authority_key: Pubkey::default(),
+            header_len: 0,
+            header: &[],
+            option_a_len: 0,
+            option_a: &[],
+            option_b_len: 0,
+            option_b: &[],
+        };
+        let mut bytes = [0; 512];
+        assert_eq!(
+            PollData::copy_to_bytes(&mut bytes, init, &key, &key, &key, u64::MAX).err(),
+            Some(DecodeError::Overflow)
+        );
     }
 }
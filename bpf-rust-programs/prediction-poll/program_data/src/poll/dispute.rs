@@ -0,0 +1,156 @@
+use crate::decode::DecodeError;
+use crate::{Encode, LeI64, LeU64};
+use prediction_poll_data_derive::Layout;
+use solana_sdk::pubkey::Pubkey;
+
+/// Smallest stake accepted to challenge an outcome, 0.1 SOL in lamports
+pub const MIN_DISPUTE_STAKE: u64 = 100_000_000;
+
+#[repr(u8)]
+#[derive(Copy, Clone, PartialEq)]
+#[cfg_attr(test, derive(Debug))]
+pub enum DisputeState {
+    Unchallenged,
+    Challenged,
+    Settled,
+    Invalid,
+}
+
+impl From<u8> for DisputeState {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => DisputeState::Unchallenged,
+            1 => DisputeState::Challenged,
+            2 => DisputeState::Settled,
+            _ => DisputeState::Invalid,
+        }
+    }
+}
+
+/// Challenge state for the outcome submitted by a poll resolver
+#[derive(Layout)]
+#[cfg_attr(test, derive(PartialEq, Debug))]
+#[layout(version = 1)]
+pub struct PollDisputeData<'a> {
+    pub window: u32, // blocks, or seconds for timestamp deadlines, after each update
+    pub authority_key: Pubkey,
+    pub state: &'a mut u8,
+    pub challenger_key: &'a mut [u8; 32],
+    pub stake: LeU64<'a>, // kept after settling when the outcome is upheld
+    pub updated_slot: LeU64<'a>, // when the outcome was submitted or challenged
+    pub updated_time: LeI64<'a>,
+}
+
+impl<'a> PollDisputeData<'a> {
+    /// Writes an unchallenged dispute and returns the rest of the buffer
+    pub fn copy_to_bytes(
+        dst: &'a mut [u8],
+        window: u32,
+        authority_key: &Pubkey,
+    ) -> Result<&'a mut [u8], DecodeError> {
+        let mut stake = 0u64.to_le_bytes();
+        let mut updated_slot = 0u64.to_le_bytes();
+        let mut updated_time = 0i64.to_le_bytes();
+        let dispute = PollDisputeData {
+            window,
+            authority_key: *authority_key,
+            state: &mut (DisputeState::Unchallenged as u8),
+            challenger_key: &mut [0; 32],
+            stake: LeU64::new(&mut stake),
+            updated_slot: LeU64::new(&mut updated_slot),
+            updated_time: LeI64::new(&mut updated_time),
+        };
+        dispute.encode(dst)
+    }
+}
+
+impl PollDisputeData<'_> {
+    /// Restarts the dispute window
+    pub fn update(&mut self, slot: u64, unix_timestamp: i64) {
+        self.updated_slot.set(slot);
+        self.updated_time.set(unix_timestamp);
+    }
+
+    pub fn challenge(&mut self, challenger_key: &Pubkey, stake: u64) {
+        *self.state = DisputeState::Challenged as u8;
+        self.challenger_key.copy_from_slice(challenger_key.as_ref());
+        self.stake.set(stake);
+    }
+
+    /// The stake is forfeited to the winners unless it is refunded
+    pub fn settle(&mut self, refund_stake: bool) {
+        *self.state = DisputeState::Settled as u8;
+        if refund_stake {
+            self.stake.set(0);
+        }
+    }
+
+    /// Stake added to the pot by a challenge that was settled in favor of the outcome
+    pub fn forfeited_stake(&self) -> u64 {
+        match DisputeState::from(*self.state) {
+            DisputeState::Settled => self.stake.get(),
+            _ => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Decode;
+
+    #[test]
+    pub fn dispute_serialization() {
+        let mut state = DisputeState::Unchallenged as u8;
+        let mut challenger_key = [0; 32];
+        let mut stake = [0; 8];
+        let mut updated_slot = [0; 8];
+        let mut updated_time = [0; 8];
+        let mut data = PollDisputeData {
+            window: 50,
+            authority_key: Pubkey::new(&[1; 32]),
+            state: &mut state,
+            challenger_key: &mut challenger_key,
+            stake: LeU64::new(&mut stake),
+            updated_slot: LeU64::new(&mut updated_slot),
+            updated_time: LeI64::new(&mut updated_time),
+        };
+        data.update(30, -1);
+        data.challenge(&Pubkey::new(&[2; 32]), 500);
+
+        let mut bytes = data.to_bytes();
+        assert_eq!(data.encoded_len(), bytes.len());
+
+        let (data_copy, _) = PollDisputeData::decode(&mut bytes[..]).unwrap();
+        assert_eq!(data, data_copy);
+        assert_eq!(
+            DisputeState::from(*data_copy.state),
+            DisputeState::Challenged
+        );
+        assert_eq!(data_copy.stake.get(), 500);
+        assert_eq!(data_copy.updated_slot.get(), 30);
+        assert_eq!(data_copy.updated_time.get(), -1);
+    }
+
+    #[test]
+    pub fn settle() {
+        let mut bytes = vec![0; 4 + 32 + 1 + 32 + 8 + 8 + 8];
+        PollDisputeData::copy_to_bytes(&mut bytes[..], 10, &Pubkey::new(&[1; 32])).unwrap();
+        let (mut data, _) = PollDisputeData::decode(&mut bytes[..]).unwrap();
+
+        assert_eq!(data.window, 10);
+        assert_eq!(DisputeState::from(*data.state), DisputeState::Unchallenged);
+
+        data.challenge(&Pubkey::new(&[2; 32]), 500);
+        assert_eq!(data.forfeited_stake(), 0);
+        data.settle(false);
+
+        assert_eq!(DisputeState::from(*data.state), DisputeState::Settled);
+        assert_eq!(data.forfeited_stake(), 500);
+        assert_eq!(*data.challenger_key, [2; 32]);
+
+        data.settle(true);
+        assert_eq!(data.stake.get(), 0);
+        assert_eq!(data.forfeited_stake(), 0);
+    }
+}
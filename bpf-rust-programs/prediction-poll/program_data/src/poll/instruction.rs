@@ -1,67 +1,44 @@
-#[cfg(any(test, feature = "wasm"))]
-use alloc::vec::Vec;
+use crate::decode::DecodeError;
+use crate::Decode;
+use prediction_poll_data_derive::Layout;
+use solana_sdk::pubkey::Pubkey;
 
+#[derive(Layout)]
 #[cfg_attr(test, derive(PartialEq, Debug))]
+#[layout(version = 1)]
 pub struct InitPollData<'a> {
-    pub timeout: u32, // block height
+    pub timeout: u32,     // block height
+    pub start_slot: u64,  // zero when the poll opens on creation
+    pub end_time: i64,    // unix timestamp, zero when the poll ends after the timeout
+    pub no_hedging: bool, // voters may only wager on one option
+    pub min_wager: u64,   // zero for no limit
+    pub max_wager: u64,   // zero for no limit
+    pub voter_cap: u64,   // total wagered by a voter, zero for no limit
+    pub creator_fee_bps: u16,
+    pub resolver_key: Pubkey, // default key when the most wagered option wins
+    pub dispute_window: u32,  // block height
+    pub authority_key: Pubkey,
     pub header_len: u32,
+    #[layout(len = header_len)]
     pub header: &'a [u8],
     pub option_a_len: u32,
+    #[layout(len = option_a_len)]
     pub option_a: &'a [u8],
     pub option_b_len: u32,
+    #[layout(len = option_b_len)]
     pub option_b: &'a [u8],
 }
 
 impl<'a> InitPollData<'a> {
-    #[cfg(any(test, feature = "wasm"))]
-    pub fn length(&self) -> usize {
-        (4 + 4 + self.header_len + 4 + self.option_a_len + 4 + self.option_b_len) as usize
-    }
-
-    #[cfg(any(test, feature = "wasm"))]
-    pub fn to_bytes(&self) -> Vec<u8> {
-        let mut bytes = Vec::with_capacity(self.length());
-        bytes.extend_from_slice(&self.timeout.to_le_bytes());
-        bytes.extend_from_slice(&self.header_len.to_le_bytes());
-        bytes.extend_from_slice(self.header);
-        bytes.extend_from_slice(&self.option_a_len.to_le_bytes());
-        bytes.extend_from_slice(self.option_a);
-        bytes.extend_from_slice(&self.option_b_len.to_le_bytes());
-        bytes.extend_from_slice(self.option_b);
-        bytes
-    }
-
-    pub fn from_bytes(data: &'a [u8]) -> Self {
-        let (timeout, data) = data.split_at(4);
-        let timeout = u32::from_le_bytes(*array_ref!(timeout, 0, 4));
-
-        let (header_len, data) = data.split_at(4);
-        let header_len = u32::from_le_bytes(*array_ref!(header_len, 0, 4));
-        let (header, data) = data.split_at(header_len as usize);
-
-        let (option_a_len, data) = data.split_at(4);
-        let option_a_len = u32::from_le_bytes(*array_ref!(option_a_len, 0, 4));
-        let (option_a, data) = data.split_at(option_a_len as usize);
-
-        let (option_b_len, data) = data.split_at(4);
-        let option_b_len = u32::from_le_bytes(*array_ref!(option_b_len, 0, 4));
-        let (option_b, _data) = data.split_at(option_b_len as usize);
-
-        InitPollData {
-            timeout,
-            header_len,
-            header,
-            option_a_len,
-            option_a,
-            option_b_len,
-            option_b,
-        }
+    pub fn from_bytes(data: &'a [u8]) -> Result<Self, DecodeError> {
+        Self::decode(data).map(|(init, _)| init)
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::Encode;
 
     #[test]
     pub fn serialization() {
@@ -70,6 +47,16 @@ mod test {
         let option_b = "second option".as_bytes();
         let data = InitPollData {
             timeout: 100,
+            start_slot: 50,
+            end_time: 1_600_000_000,
+            no_hedging: true,
+            min_wager: 10,
+            max_wager: 1000,
+            voter_cap: 2000,
+            creator_fee_bps: 150,
+            resolver_key: Pubkey::new(&[1; 32]),
+            dispute_window: 20,
+            authority_key: Pubkey::new(&[2; 32]),
             header_len: header.len() as u32,
             header,
             option_a_len: option_a.len() as u32,
@@ -79,9 +66,43 @@ mod test {
         };
 
         let bytes = data.to_bytes();
-        let data_copy = InitPollData::from_bytes(&bytes[..]);
+        let data_copy = InitPollData::from_bytes(&bytes[..]).unwrap();
 
         assert_eq!(data, data_copy);
-        assert_eq!(data.length(), bytes.len());
+        assert_eq!(data.encoded_len(), bytes.len());
+    }
+
+    #[test]
+    pub fn malformed() {
+        let header = "poll".as_bytes();
+        let data = InitPollData {
+            timeout: 100,
+            start_slot: 0,
+            end_time: 0,
+            no_hedging: false,
+            min_wager: 0,
+            max_wager: 0,
+            voter_cap: 0,
+            creator_fee_bps: 0,
+            resolver_key: Pubkey::default(),
+            dispute_window: 0,
+            authority_key: Pubkey::default(),
+            header_len: 1000,
+            header,
+            option_a_len: 0,
+            option_a: &[],
+            option_b_len: 0,
+            option_b: &[],
+        };
+
+        let bytes = data.to_bytes();
+        assert_eq!(
+            InitPollData::from_bytes(&bytes[..]),
+            Err(DecodeError::UnexpectedEnd)
+        );
+        assert_eq!(
+            InitPollData::from_bytes(&bytes[..10]),
+            Err(DecodeError::UnexpectedEnd)
+        );
     }
 }
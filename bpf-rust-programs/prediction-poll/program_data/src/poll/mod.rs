@@ -1,5 +1,7 @@
 mod data;
+mod dispute;
 mod instruction;
 
 pub use data::*;
+pub use dispute::*;
 pub use instruction::*;
@@ -0,0 +1,198 @@
+use crate::{PollData, PollOutcome, TallyData};
+use core::convert::TryFrom;
+use solana_sdk::pubkey::Pubkey;
+
+/// Fees are expressed in basis points of the pot
+pub const MAX_FEE_BPS: u16 = 10_000;
+
+/// Program-wide fee charged on every poll created by this build of the program,
+/// zero disables it. Deployments charging it set both constants before building.
+pub const PROTOCOL_FEE_BPS: u16 = 0;
+
+/// Key of the account the protocol fee is paid to
+pub const PROTOCOL_TREASURY: [u8; 32] = [0; 32];
+
+/// Creator and protocol fees charged on an amount paid out of the pot
+pub fn fees(amount: u64, creator_fee_bps: u16, protocol_fee_bps: u16) -> (u64, u64) {
+    let fee = |bps: u16| {
+        let fee = u128::from(amount) * u128::from(bps) / u128::from(MAX_FEE_BPS);
+        fee as u64 // fee <= amount since bps <= MAX_FEE_BPS
+    };
+    (fee(creator_fee_bps), fee(protocol_fee_bps))
+}
+
+/// Part of the pot owed to a wager on the winning option, zero if nothing was wagered
+///
+/// Returns `None` if the wager is larger than the winning quantity and its share
+/// doesn't fit in a `u64`.
+pub fn share(pot: u64, wager: u64, winning_quantity: u64) -> Option<u64> {
+    (u128::from(pot) * u128::from(wager))
+        .checked_div(u128::from(winning_quantity))
+        .map_or(Some(0), |share| u64::try_from(share).ok())
+}
+
+/// Total wagered by a voter on a tally page
+pub fn wager_of(tally: &TallyData, user_key: &Pubkey) -> u64 {
+    tally
+        .position(user_key)
+        .map_or(0, |index| tally.wager(index))
+}
+
+/// Snapshot of a poll's pot used to quote parimutuel odds and payouts
+#[derive(Clone, Copy)]
+#[cfg_attr(test, derive(PartialEq, Debug))]
+pub struct PollQuote {
+    pub quantity_a: u64,
+    pub quantity_b: u64,
+    pub creator_fee_bps: u16,
+    pub protocol_fee_bps: u16,
+    pub forfeited_stake: u64, // upheld challenges pay their stake to the winners
+}
+
+impl From<&PollData<'_>> for PollQuote {
+    fn from(poll: &PollData) -> Self {
+        Self {
+            quantity_a: poll.option_a.quantity.get(),
+            quantity_b: poll.option_b.quantity.get(),
+            creator_fee_bps: poll.creator_fee_bps,
+            protocol_fee_bps: poll.protocol_fee_bps,
+            forfeited_stake: poll.dispute.forfeited_stake(),
+        }
+    }
+}
+
+impl PollQuote {
+    /// Voting keeps the pot within a `u64`, quotes of hypothetical wagers saturate
+    pub fn pot(&self) -> u64 {
+        self.quantity_a
+            .saturating_add(self.quantity_b)
+            .saturating_add(self.forfeited_stake)
+    }
+
+    pub fn fees(&self, amount: u64) -> (u64, u64) {
+        fees(amount, self.creator_fee_bps, self.protocol_fee_bps)
+    }
+
+    pub fn fee_adjusted_pot(&self) -> u64 {
+        self.after_fees(self.pot())
+    }
+
+    /// Amount left after fees, fees never exceed the amount since bps <= MAX_FEE_BPS
+    pub fn after_fees(&self, amount: u64) -> u64 {
+        let (creator_fee, protocol_fee) = self.fees(amount);
+        amount
+            .saturating_sub(creator_fee)
+            .saturating_sub(protocol_fee)
+    }
+
+    pub fn quantity(&self, option: PollOutcome) -> Option<u64> {
+        match option {
+            PollOutcome::OptionA => Some(self.quantity_a),
+            PollOutcome::OptionB => Some(self.quantity_b),
+            _ => None,
+        }
+    }
+
+    /// Payout per unit wagered if the option wins, in basis points
+    pub fn odds_bps(&self, option: PollOutcome) -> Option<u64> {
+        let quantity = self.quantity(option)?;
+        if quantity == 0 {
+            return None;
+        }
+        share(self.fee_adjusted_pot(), u64::from(MAX_FEE_BPS), quantity)
+    }
+
+    /// Amount received for a wager already in the pot if the option wins, after fees
+    pub fn payout(&self, option: PollOutcome, wager: u64) -> u64 {
+        let portion = share(self.pot(), wager, self.quantity(option).unwrap_or(0)).unwrap_or(0);
+        self.after_fees(portion)
+    }
+
+    /// Pot after a new wager on the option
+    pub fn with_wager(&self, option: PollOutcome, wager: u64) -> Self {
+        let mut quote = *self;
+        match option {
+            PollOutcome::OptionA => quote.quantity_a = quote.quantity_a.saturating_add(wager),
+            PollOutcome::OptionB => quote.quantity_b = quote.quantity_b.saturating_add(wager),
+            _ => {}
+        }
+        quote
+    }
+
+    /// Amount a voter would receive if they wagered now and the option wins
+    pub fn projected_payout(&self, option: PollOutcome, existing_wager: u64, wager: u64) -> u64 {
+        self.with_wager(option, wager)
+            .payout(option, existing_wager.saturating_add(wager))
+    }
+}
+
+/// Projected payout for a voter adding a wager to the option of a tally page
+pub fn projected_payout(poll: &PollData, tally: &TallyData, user_key: &Pubkey, wager: u64) -> u64 {
+    PollQuote::from(poll).projected_payout(tally.option, wager_of(tally, user_key), wager)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn quote() -> PollQuote {
+        PollQuote {
+            quantity_a: 300,
+            quantity_b: 100,
+            creator_fee_bps: 100,
+            protocol_fee_bps: 150,
+            forfeited_stake: 0,
+        }
+    }
+
+    #[test]
+    pub fn fee_adjusted_pot() {
+        assert_eq!(fees(1000, 100, 150), (10, 15));
+        assert_eq!(quote().pot(), 400);
+        assert_eq!(quote().fee_adjusted_pot(), 390);
+
+        let upheld = PollQuote {
+            forfeited_stake: 100,
+            ..quote()
+        };
+        assert_eq!(upheld.pot(), 500);
+        assert_eq!(upheld.payout(PollOutcome::OptionB, 100), 488);
+    }
+
+    #[test]
+    pub fn odds() {
+        assert_eq!(quote().odds_bps(PollOutcome::OptionA), Some(13_000));
+        assert_eq!(quote().odds_bps(PollOutcome::OptionB), Some(39_000));
+        assert_eq!(quote().odds_bps(PollOutcome::Cancelled), None);
+
+        let empty = PollQuote {
+            quantity_b: 0,
+            ..quote()
+        };
+        assert_eq!(empty.odds_bps(PollOutcome::OptionB), None);
+
+        let whale = PollQuote {
+            quantity_a: u64::MAX,
+            ..quote()
+        };
+        assert_eq!(whale.pot(), u64::MAX);
+        assert_eq!(whale.odds_bps(PollOutcome::OptionB), None);
+    }
+
+    #[test]
+    pub fn payouts() {
+        assert_eq!(share(400, 100, 300), Some(133));
+        assert_eq!(share(400, 100, 0), Some(0));
+        assert_eq!(share(u64::MAX, 2, 1), None);
+
+        // 100 of 300 on A wins 133 of the pot, less 2 in fees
+        assert_eq!(quote().payout(PollOutcome::OptionA, 100), 131);
+
+        // 100 more on B makes it 200 of a 500 pot
+        assert_eq!(quote().projected_payout(PollOutcome::OptionB, 0, 100), 245);
+        assert_eq!(
+            quote().projected_payout(PollOutcome::OptionB, 100, 100),
+            488
+        );
+    }
+}
@@ -1,67 +1,155 @@
-use crate::DataType;
-use alloc::slice::from_raw_parts_mut;
+use crate::decode::DecodeError;
+use crate::{AccountHeader, DataType, Decode, Encode, LeU32, PollOutcome, Versioned};
+use prediction_poll_data_derive::Layout;
 use solana_sdk::pubkey::Pubkey;
 
-type Tally = [u8; 40]; // Pubkey, u64
+type Tally = [u8; 41]; // Pubkey, u64, claimed flag
 
 /// Min data size for a tally
-/// Breakdown: data_type (1) + tally_count (4) + one tally (40)
-pub const MIN_TALLY_SIZE: usize = 1 + 4 + 40;
+/// Breakdown: data_type (1) + version (1) + poll_key (32) + option (1) + next_tally (32)
+///     + tally_count (4) + one tally (41)
+pub const MIN_TALLY_SIZE: usize = 1 + 1 + 32 + 1 + 32 + 4 + 41;
 
+/// A page of wagers for one poll option, full pages link to an overflow page
+#[derive(Layout)]
+#[layout(version = 2)]
 pub struct TallyData<'a> {
-    pub data_type: DataType,
-    pub tally_count: &'a mut u32,
-    pub tallies: &'a mut [Tally],
+    pub account: AccountHeader,
+    pub poll_key: Pubkey,
+    pub option: PollOutcome,
+    next_tally: &'a mut [u8; 32],
+    tally_count: LeU32<'a>,
+    #[layout(chunks = 41)]
+    tallies: &'a mut [u8],
 }
 
 impl<'a> TallyData<'a> {
-    pub fn from_bytes(data: &'a mut [u8]) -> Self {
-        let (data_type, data) = data.split_at_mut(1);
-        let (tally_count, data) = data.split_at_mut(4);
-        #[allow(clippy::cast_ptr_alignment)]
-        let tally_count = unsafe { &mut *(&mut tally_count[0] as *mut u8 as *mut u32) };
-        Self {
-            data_type: DataType::from(data_type[0]),
-            tally_count,
-            tallies: unsafe {
-                from_raw_parts_mut(&mut data[0] as *mut u8 as *mut _, data.len() / 40)
-            },
-        }
+    /// Writes the header of an empty tally page
+    pub fn copy_to_bytes(
+        dst: &'a mut [u8],
+        poll_key: &Pubkey,
+        option: PollOutcome,
+    ) -> Result<(), DecodeError> {
+        let mut tally_count = 0u32.to_le_bytes();
+        let tally = TallyData {
+            account: AccountHeader::new(DataType::Tally, Self::VERSION),
+            poll_key: *poll_key,
+            option,
+            next_tally: &mut [0; 32],
+            tally_count: LeU32::new(&mut tally_count),
+            tallies: &mut [],
+        };
+        tally.encode(dst)?;
+        Ok(())
+    }
+
+    pub fn from_bytes(data: &'a mut [u8]) -> Result<Self, DecodeError> {
+        AccountHeader::from_bytes(data)?.expect_version(Self::VERSION)?;
+        let (tally, _) = Self::decode(data)?;
+        Ok(tally)
     }
 }
 
 impl TallyData<'_> {
+    fn tally(&self, index: usize) -> &Tally {
+        array_ref!(self.tallies, index * 41, 41)
+    }
+
+    fn tally_mut(&mut self, index: usize) -> &mut Tally {
+        array_mut_ref!(self.tallies, index * 41, 41)
+    }
+
+    pub fn next_tally(&self) -> Option<Pubkey> {
+        if *self.next_tally == [0; 32] {
+            None
+        } else {
+            Some(Pubkey::new(&self.next_tally[..]))
+        }
+    }
+
+    pub fn set_next_tally(&mut self, tally: &Pubkey) {
+        self.next_tally.copy_from_slice(tally.as_ref());
+    }
+
+    /// Points the links to an account at its new key, returning how many changed
+    pub fn relink(&mut self, old: &Pubkey, new: &Pubkey) -> usize {
+        let mut links = 0;
+        if self.poll_key == *old {
+            self.poll_key = *new;
+            links += 1;
+        }
+        if self.next_tally() == Some(*old) {
+            self.set_next_tally(new);
+            links += 1;
+        }
+        links
+    }
+
+    /// Bytes holding the header and stored tallies
+    pub fn used_len(&self) -> usize {
+        self.encoded_len() - self.tallies.len() + self.len() * 41
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len() >= self.capacity()
+    }
+
     pub fn get_wager_mut(&mut self, user_key: &Pubkey) -> Option<&mut [u8; 8]> {
         for t in 0..self.len() {
-            let key = Pubkey::new(array_ref!(self.tallies[t], 0, 32));
+            let key = Pubkey::new(array_ref!(self.tally(t), 0, 32));
             if key == *user_key {
-                return Some(array_mut_ref!(self.tallies[t], 32, 8));
+                return Some(array_mut_ref!(self.tally_mut(t), 32, 8));
             }
         }
         None
     }
 
+    pub fn position(&self, user_key: &Pubkey) -> Option<usize> {
+        let user_key_data = array_ref!(user_key.as_ref(), 0, 32);
+        (0..self.len()).position(|index| array_ref!(self.tally(index), 0, 32) == user_key_data)
+    }
+
+    pub fn key(&self, index: usize) -> Pubkey {
+        Pubkey::new(&self.tally(index)[..32])
+    }
+
+    pub fn wager(&self, index: usize) -> u64 {
+        u64::from_le_bytes(*array_ref!(self.tally(index), 32, 8))
+    }
+
+    pub fn is_claimed(&self, index: usize) -> bool {
+        self.tally(index)[40] != 0
+    }
+
+    pub fn set_claimed(&mut self, index: usize) {
+        self.tally_mut(index)[40] = 1;
+    }
+
     pub fn capacity(&self) -> usize {
-        self.tallies.len()
+        self.tallies.len() / 41
     }
 
     pub fn is_empty(&self) -> bool {
         self.len() == 0
     }
 
+    /// Stored counts past the capacity are clamped so corrupt data can't index out of bounds
     pub fn len(&self) -> usize {
-        *self.tally_count as usize
+        (self.tally_count.get() as usize).min(self.capacity())
     }
 
     pub fn add_tally(&mut self, user_key: &Pubkey, wager: u64) {
         let next_tally = self.len();
-        self.tallies[next_tally][..32].copy_from_slice(user_key.as_ref());
-        self.tallies[next_tally][32..].copy_from_slice(&wager.to_le_bytes());
-        *self.tally_count += 1;
+        let tally = self.tally_mut(next_tally);
+        tally[..32].copy_from_slice(user_key.as_ref());
+        tally[32..40].copy_from_slice(&wager.to_le_bytes());
+        tally[40] = 0;
+        self.tally_count.set(self.tally_count.get() + 1);
     }
 
     pub fn iter(&self) -> impl Iterator<Item = (Pubkey, u64)> + '_ {
-        self.tallies[..self.len()].iter().map(|tally| {
+        (0..self.len()).map(move |index| {
+            let tally = self.tally(index);
             let key = Pubkey::new(&tally[..32]);
             let wager = u64::from_le_bytes(*array_ref!(tally, 32, 8));
             (key, wager)
@@ -78,7 +166,8 @@ mod test {
         let user_key = Pubkey::new(&[0; 32]);
         let wager = 100;
         let mut data = vec![0; MIN_TALLY_SIZE];
-        let mut tally = TallyData::from_bytes(&mut data[..]);
+        TallyData::copy_to_bytes(&mut data[..], &Pubkey::default(), PollOutcome::OptionA).unwrap();
+        let mut tally = TallyData::from_bytes(&mut data[..]).unwrap();
 
         assert_eq!(tally.len(), 0);
         assert_eq!(tally.capacity(), 1);
@@ -98,4 +187,63 @@ mod test {
         assert_eq!(tally_iter.next(), Some((user_key, wager)));
         assert_eq!(tally_iter.next(), None);
     }
+
+    #[test]
+    pub fn claim_tally() {
+        let user_key = Pubkey::new(&[1; 32]);
+        let other_key = Pubkey::new(&[2; 32]);
+        let mut data = vec![0; MIN_TALLY_SIZE + 41];
+        TallyData::copy_to_bytes(&mut data[..], &Pubkey::default(), PollOutcome::OptionA).unwrap();
+        let mut tally = TallyData::from_bytes(&mut data[..]).unwrap();
+
+        tally.add_tally(&other_key, 50);
+        tally.add_tally(&user_key, 100);
+
+        assert_eq!(tally.position(&user_key), Some(1));
+        assert_eq!(tally.position(&Pubkey::new(&[3; 32])), None);
+        assert_eq!(tally.key(1), user_key);
+        assert_eq!(tally.wager(1), 100);
+        assert!(!tally.is_claimed(1));
+
+        tally.set_claimed(1);
+
+        assert!(tally.is_claimed(1));
+        assert!(!tally.is_claimed(0));
+        assert_eq!(tally.wager(1), 100);
+        assert_eq!(tally.iter().nth(1), Some((user_key, 100)));
+    }
+
+    #[test]
+    pub fn tally_pages() {
+        let poll_key = Pubkey::new(&[1; 32]);
+        let next_key = Pubkey::new(&[2; 32]);
+        let mut data = vec![0xff; MIN_TALLY_SIZE];
+        TallyData::copy_to_bytes(&mut data[..], &poll_key, PollOutcome::OptionB).unwrap();
+        let mut tally = TallyData::from_bytes(&mut data[..]).unwrap();
+
+        assert_eq!(tally.account, AccountHeader::new(DataType::Tally, 2));
+        assert_eq!(tally.poll_key, poll_key);
+        assert_eq!(tally.option, PollOutcome::OptionB);
+        assert_eq!(tally.next_tally(), None);
+        assert!(tally.is_empty());
+        assert!(!tally.is_full());
+
+        tally.add_tally(&Pubkey::new(&[3; 32]), 100);
+        assert!(tally.is_full());
+
+        tally.set_next_tally(&next_key);
+        assert_eq!(tally.next_tally(), Some(next_key));
+    }
+
+    #[test]
+    pub fn corrupt_count() {
+        let mut data = vec![0; MIN_TALLY_SIZE];
+        TallyData::copy_to_bytes(&mut data[..], &Pubkey::default(), PollOutcome::OptionA).unwrap();
+        data[67..71].copy_from_slice(&u32::MAX.to_le_bytes());
+        let tally = TallyData::from_bytes(&mut data[..]).unwrap();
+
+        assert_eq!(tally.len(), 1);
+        assert!(tally.is_full());
+        assert_eq!(tally.iter().count(), 1);
+    }
 }
@@ -0,0 +1,190 @@
+//! `#[derive(Layout)]` for the account and instruction data in `prediction-poll-data`
+//!
+//! Fields are encoded in declaration order with the field encodings from the
+//! data crate's `layout` module, so the derive is only meant for use inside it.
+//! Byte slice fields take their extent from an attribute:
+//!
+//! - `#[layout(len = field)]` reads as many bytes as an earlier length field
+//! - `#[layout(chunks = n)]` reads the rest of the data trimmed to a multiple of `n`
+//!
+//! Every layout is versioned with `#[layout(version = n)]` on the struct.
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::parse::ParseStream;
+use syn::{
+    parse_macro_input, parse_quote, Attribute, Data, DeriveInput, Error, Fields, GenericParam,
+    Ident, Lifetime, LifetimeDef, LitInt, Token, Type,
+};
+
+enum Extent {
+    Field,
+    Len(Ident),
+    Chunks(LitInt),
+}
+
+struct LayoutField {
+    ident: Ident,
+    ty: Type,
+    extent: Extent,
+}
+
+#[proc_macro_derive(Layout, attributes(layout))]
+pub fn derive_layout(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// Parses the `key = value, ...` arguments of every `#[layout(...)]` attribute,
+/// leaving the value to `arg` once it has matched the key
+fn parse_layout_args(
+    attrs: &[Attribute],
+    mut arg: impl FnMut(&Ident, ParseStream) -> syn::Result<()>,
+) -> syn::Result<()> {
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("layout")) {
+        attr.parse_args_with(|input: ParseStream| {
+            while !input.is_empty() {
+                let key: Ident = input.parse()?;
+                input.parse::<Token![=]>()?;
+                arg(&key, input)?;
+                if !input.is_empty() {
+                    input.parse::<Token![,]>()?;
+                }
+            }
+            Ok(())
+        })?;
+    }
+    Ok(())
+}
+
+fn version(input: &DeriveInput) -> syn::Result<LitInt> {
+    let mut version = None;
+    parse_layout_args(&input.attrs, |key, value| {
+        if key == "version" {
+            version = Some(value.parse()?);
+            Ok(())
+        } else {
+            Err(Error::new(key.span(), "expected `version`"))
+        }
+    })?;
+    version.ok_or_else(|| Error::new_spanned(&input.ident, "missing #[layout(version = n)]"))
+}
+
+fn fields(input: &DeriveInput) -> syn::Result<Vec<LayoutField>> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => return Err(Error::new_spanned(&input.ident, "expected named fields")),
+        },
+        _ => return Err(Error::new_spanned(&input.ident, "expected a struct")),
+    };
+
+    fields
+        .iter()
+        .map(|field| {
+            let mut extent = Extent::Field;
+            parse_layout_args(&field.attrs, |key, value| {
+                if key == "len" {
+                    extent = Extent::Len(value.parse()?);
+                    Ok(())
+                } else if key == "chunks" {
+                    extent = Extent::Chunks(value.parse()?);
+                    Ok(())
+                } else {
+                    Err(Error::new(key.span(), "expected `len` or `chunks`"))
+                }
+            })?;
+            Ok(LayoutField {
+                ident: field.ident.clone().unwrap(),
+                ty: field.ty.clone(),
+                extent,
+            })
+        })
+        .collect()
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let version = version(input)?;
+    let fields = fields(input)?;
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    // Decoded fields borrow from the data for the struct's lifetime
+    let mut decode_generics = input.generics.clone();
+    let lifetime = match input.generics.lifetimes().next() {
+        Some(param) => param.lifetime.clone(),
+        None => {
+            let lifetime = Lifetime::new("'a", Span::call_site());
+            let param = GenericParam::Lifetime(LifetimeDef::new(lifetime.clone()));
+            decode_generics.params.insert(0, param);
+            lifetime
+        }
+    };
+    decode_generics
+        .params
+        .push(parse_quote!(__B: crate::Bytes<#lifetime>));
+    let predicates = &mut decode_generics.make_where_clause().predicates;
+    for field in &fields {
+        let ty = &field.ty;
+        predicates.push(parse_quote!(#ty: crate::Decode<#lifetime, __B>));
+    }
+    let (decode_impl_generics, _, decode_where_clause) = decode_generics.split_for_impl();
+
+    let decode_fields = fields.iter().map(|field| {
+        let ident = &field.ident;
+        let ty = &field.ty;
+        let decode = quote! { <#ty as crate::Decode<#lifetime, __B>>::decode };
+        match &field.extent {
+            Extent::Field => quote! {
+                let (#ident, rest) = #decode(data)?;
+                data = rest;
+            },
+            Extent::Len(len) => quote! {
+                let (#ident, rest) = crate::Bytes::split_at(data, #len as usize)?;
+                data = rest;
+                let (#ident, _) = #decode(#ident)?;
+            },
+            Extent::Chunks(size) => quote! {
+                let len = crate::Bytes::len(&data);
+                let (#ident, rest) = crate::Bytes::split_at(data, len - len % #size)?;
+                data = rest;
+                let (#ident, _) = #decode(#ident)?;
+            },
+        }
+    });
+    let idents: Vec<_> = fields.iter().map(|field| &field.ident).collect();
+
+    Ok(quote! {
+        impl #decode_impl_generics crate::Decode<#lifetime, __B> for #name #ty_generics
+        #decode_where_clause
+        {
+            fn decode(mut data: __B) -> Result<(Self, __B), crate::DecodeError> {
+                #(#decode_fields)*
+                Ok((Self { #(#idents),* }, data))
+            }
+        }
+
+        impl #impl_generics crate::Encode for #name #ty_generics #where_clause {
+            fn encoded_len(&self) -> usize {
+                0 #(+ crate::Encode::encoded_len(&self.#idents))*
+            }
+
+            fn encode<'__dst>(
+                &self,
+                dst: &'__dst mut [u8],
+            ) -> Result<&'__dst mut [u8], crate::DecodeError> {
+                #(let dst = crate::Encode::encode(&self.#idents, dst)?;)*
+                Ok(dst)
+            }
+        }
+
+        impl #impl_generics crate::Versioned for #name #ty_generics #where_clause {
+            const VERSION: u8 = #version;
+        }
+    })
+}
@@ -1,19 +1,26 @@
-use core::convert::TryFrom;
+use crate::decode_error;
 use prediction_poll_data::ClockData;
 use wasm_bindgen::prelude::*;
 
 #[wasm_bindgen]
 pub struct Clock {
-    pub slot: u32, // u64, https://caniuse.com/#feat=bigint
+    pub slot: f64, // u64 as a js number, exact up to 2^53
+    pub epoch: f64,
+    pub leader_schedule_epoch: f64,
+    pub unix_timestamp: f64, // i64 as a js number
 }
 
 #[wasm_bindgen]
 impl Clock {
     #[wasm_bindgen(js_name = fromData)]
-    pub fn from_data(val: &[u8]) -> Self {
+    pub fn from_data(val: &[u8]) -> Result<Clock, JsValue> {
         console_error_panic_hook::set_once();
-        Clock {
-            slot: u32::try_from(ClockData::from_bytes(val).slot).unwrap(),
-        }
+        let clock = ClockData::from_bytes(val).map_err(decode_error)?;
+        Ok(Clock {
+            slot: clock.slot as f64,
+            epoch: clock.epoch as f64,
+            leader_schedule_epoch: clock.leader_schedule_epoch as f64,
+            unix_timestamp: clock.unix_timestamp as f64,
+        })
     }
 }
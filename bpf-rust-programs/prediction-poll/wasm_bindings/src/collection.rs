@@ -1,3 +1,4 @@
+use crate::decode_error;
 use alloc::boxed::Box;
 use alloc::vec::Vec;
 use js_sys::Uint8Array;
@@ -8,12 +9,14 @@ use wasm_bindgen::prelude::*;
 #[wasm_bindgen]
 pub struct Collection {
     polls: Vec<Pubkey>,
+    next_collection: Option<Pubkey>,
 }
 
 impl From<CollectionData<'_>> for Collection {
     fn from(collection_data: CollectionData) -> Self {
         Collection {
             polls: collection_data.to_vec(),
+            next_collection: collection_data.next_collection(),
         }
     }
 }
@@ -35,9 +38,19 @@ impl Collection {
         self.polls.len()
     }
 
+    #[wasm_bindgen(js_name = getNextCollection)]
+    pub fn get_next_collection(&self) -> JsValue {
+        match self.next_collection {
+            Some(key) => Uint8Array::from(key.as_ref()).into(),
+            None => JsValue::NULL,
+        }
+    }
+
     #[wasm_bindgen(js_name = fromData)]
-    pub fn from_data(val: &mut [u8]) -> Self {
+    pub fn from_data(val: &mut [u8]) -> Result<Collection, JsValue> {
         console_error_panic_hook::set_once();
-        CollectionData::from_bytes(val).into()
+        CollectionData::from_bytes(val)
+            .map(Into::into)
+            .map_err(decode_error)
     }
 }
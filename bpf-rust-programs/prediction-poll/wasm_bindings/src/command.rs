@@ -1,6 +1,6 @@
 use crate::InitPoll;
 use alloc::boxed::Box;
-use prediction_poll_data::CommandData;
+use prediction_poll_data::{CommandData, Encode};
 use wasm_bindgen::prelude::*;
 
 #[wasm_bindgen]
@@ -13,6 +13,16 @@ impl Command {
         vec![(CommandData::InitCollection as u8).to_le()].into_boxed_slice()
     }
 
+    #[wasm_bindgen(js_name = closePoll)]
+    pub fn close_poll() -> Box<[u8]> {
+        vec![(CommandData::ClosePoll as u8).to_le()].into_boxed_slice()
+    }
+
+    #[wasm_bindgen(js_name = extendCollection)]
+    pub fn extend_collection() -> Box<[u8]> {
+        vec![(CommandData::ExtendCollection as u8).to_le()].into_boxed_slice()
+    }
+
     #[wasm_bindgen(js_name = initPoll)]
     pub fn init_poll(init_poll: InitPoll) -> Box<[u8]> {
         let mut bytes = init_poll.to_data().to_bytes();
@@ -20,6 +30,46 @@ impl Command {
         bytes.into_boxed_slice()
     }
 
+    #[wasm_bindgen(js_name = claimPayout)]
+    pub fn claim_payout() -> Box<[u8]> {
+        vec![(CommandData::ClaimPayout as u8).to_le()].into_boxed_slice()
+    }
+
+    #[wasm_bindgen(js_name = resolvePoll)]
+    pub fn resolve_poll(outcome: u8) -> Box<[u8]> {
+        vec![(CommandData::ResolvePoll as u8).to_le(), outcome].into_boxed_slice()
+    }
+
+    #[wasm_bindgen(js_name = addTallyPage)]
+    pub fn add_tally_page() -> Box<[u8]> {
+        vec![(CommandData::AddTallyPage as u8).to_le()].into_boxed_slice()
+    }
+
+    #[wasm_bindgen(js_name = cancelPoll)]
+    pub fn cancel_poll() -> Box<[u8]> {
+        vec![(CommandData::CancelPoll as u8).to_le()].into_boxed_slice()
+    }
+
+    #[wasm_bindgen(js_name = challengeOutcome)]
+    pub fn challenge_outcome() -> Box<[u8]> {
+        vec![(CommandData::ChallengeOutcome as u8).to_le()].into_boxed_slice()
+    }
+
+    #[wasm_bindgen(js_name = migrate)]
+    pub fn migrate() -> Box<[u8]> {
+        vec![(CommandData::Migrate as u8).to_le()].into_boxed_slice()
+    }
+
+    #[wasm_bindgen(js_name = refundWagers)]
+    pub fn refund_wagers() -> Box<[u8]> {
+        vec![(CommandData::RefundWagers as u8).to_le()].into_boxed_slice()
+    }
+
+    #[wasm_bindgen(js_name = settleDispute)]
+    pub fn settle_dispute(outcome: u8) -> Box<[u8]> {
+        vec![(CommandData::SettleDispute as u8).to_le(), outcome].into_boxed_slice()
+    }
+
     #[wasm_bindgen(js_name = submitClaim)]
     pub fn submit_claim() -> Box<[u8]> {
         vec![(CommandData::SubmitClaim as u8).to_le()].into_boxed_slice()
@@ -1,6 +1,7 @@
 use alloc::boxed::Box;
 use alloc::string::String;
-use prediction_poll_data::InitPollData;
+use prediction_poll_data::{Encode, InitPollData};
+use solana_sdk::pubkey::Pubkey;
 use wasm_bindgen::prelude::*;
 
 #[wasm_bindgen]
@@ -9,20 +10,76 @@ pub struct InitPoll {
     option_a: String,
     option_b: String,
     timeout: u32,
+    start_slot: u64,
+    end_time: i64,
+    no_hedging: bool,
+    min_wager: u64,
+    max_wager: u64,
+    voter_cap: u64,
+    creator_fee_bps: u16,
+    resolver_key: Pubkey,
+    dispute_window: u32,
+    authority_key: Pubkey,
 }
 
 #[wasm_bindgen]
 impl InitPoll {
     #[wasm_bindgen(constructor)]
-    pub fn new(header: String, option_a: String, option_b: String, timeout: u32) -> Self {
+    pub fn new(
+        header: String,
+        option_a: String,
+        option_b: String,
+        timeout: u32,
+        creator_fee_bps: u16,
+        resolver_key: Option<Box<[u8]>>,
+    ) -> Self {
         Self {
             header,
             option_a,
             option_b,
             timeout,
+            start_slot: 0,
+            end_time: 0,
+            no_hedging: false,
+            min_wager: 0,
+            max_wager: 0,
+            voter_cap: 0,
+            creator_fee_bps,
+            resolver_key: resolver_key.map_or_else(Pubkey::default, |key| Pubkey::new(&key)),
+            dispute_window: 0,
+            authority_key: Pubkey::default(),
         }
     }
 
+    #[wasm_bindgen(js_name = setStartSlot)]
+    pub fn set_start_slot(&mut self, start_slot: f64) {
+        self.start_slot = start_slot as u64;
+    }
+
+    #[wasm_bindgen(js_name = setEndTime)]
+    pub fn set_end_time(&mut self, end_time: f64) {
+        self.end_time = end_time as i64;
+    }
+
+    #[wasm_bindgen(js_name = setNoHedging)]
+    pub fn set_no_hedging(&mut self, no_hedging: bool) {
+        self.no_hedging = no_hedging;
+    }
+
+    /// Amounts are in lamports, js numbers are exact up to 2^53
+    #[wasm_bindgen(js_name = setWagerLimits)]
+    pub fn set_wager_limits(&mut self, min_wager: f64, max_wager: f64, voter_cap: f64) {
+        self.min_wager = min_wager as u64;
+        self.max_wager = max_wager as u64;
+        self.voter_cap = voter_cap as u64;
+    }
+
+    #[wasm_bindgen(js_name = setDisputeWindow)]
+    pub fn set_dispute_window(&mut self, dispute_window: u32, authority_key: &[u8]) {
+        self.dispute_window = dispute_window;
+        self.authority_key = Pubkey::new(authority_key);
+    }
+
     pub(crate) fn to_data(&self) -> InitPollData<'_> {
         let timeout = self.timeout;
 
@@ -37,6 +94,16 @@ impl InitPoll {
 
         InitPollData {
             timeout,
+            start_slot: self.start_slot,
+            end_time: self.end_time,
+            no_hedging: self.no_hedging,
+            min_wager: self.min_wager,
+            max_wager: self.max_wager,
+            voter_cap: self.voter_cap,
+            creator_fee_bps: self.creator_fee_bps,
+            resolver_key: self.resolver_key,
+            dispute_window: self.dispute_window,
+            authority_key: self.authority_key,
             header_len,
             header,
             option_a_len,
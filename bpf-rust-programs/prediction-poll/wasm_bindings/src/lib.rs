@@ -17,3 +17,27 @@ pub use collection::*;
 pub use init_poll::*;
 pub use poll::*;
 pub use tally::*;
+
+/// Converts data decoding errors into exceptions thrown from `fromData`
+pub(crate) fn decode_error(err: prediction_poll_data::DecodeError) -> wasm_bindgen::JsValue {
+    use prediction_poll_data::DecodeError;
+    wasm_bindgen::JsValue::from_str(match err {
+        DecodeError::UnexpectedEnd => "Data ended unexpectedly",
+        DecodeError::Overflow => "Data value overflowed",
+        DecodeError::UnsupportedVersion => "Data layout version is unsupported",
+        DecodeError::InvalidDataType => "Data type is invalid",
+    })
+}
+
+/// Converts account text into a string, throwing from `fromData` if it is not UTF-8
+pub(crate) fn decode_text(text: &[u8]) -> Result<alloc::string::String, wasm_bindgen::JsValue> {
+    core::str::from_utf8(text)
+        .map(alloc::string::ToString::to_string)
+        .map_err(|_| wasm_bindgen::JsValue::from_str("Data text is not valid UTF-8"))
+}
+
+/// Describes a custom error code returned by a failed prediction poll transaction
+#[wasm_bindgen::prelude::wasm_bindgen(js_name = pollErrorMessage)]
+pub fn poll_error_message(code: u32) -> Option<alloc::string::String> {
+    prediction_poll_data::poll_error_message(code).map(alloc::string::ToString::to_string)
+}
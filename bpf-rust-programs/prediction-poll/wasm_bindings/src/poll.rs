@@ -1,55 +1,118 @@
+use crate::{decode_error, decode_text};
 use alloc::borrow::ToOwned;
-use alloc::string::{String, ToString};
+use alloc::string::String;
 use core::convert::TryFrom;
-use core::str::from_utf8;
 use js_sys::Uint8Array;
-use prediction_poll_data::PollData;
+use prediction_poll_data::{PollData, PollOutcome, PollQuote, PROTOCOL_TREASURY};
 use solana_sdk::pubkey::Pubkey;
 use wasm_bindgen::prelude::*;
 
 #[wasm_bindgen]
 pub struct Poll {
     creator_key: Pubkey,
+    resolver_key: Option<Pubkey>,
+    challenger_key: Pubkey,
     header: String,
     option_a: PollOption,
     option_b: PollOption,
-    pub last_block: u32, // u64, https://caniuse.com/#feat=bigint
+    quote: PollQuote,
+    pub first_block: f64, // u64 as a js number, exact up to 2^53
+    pub last_block: f64,  // u64 as a js number, exact up to 2^53
+    pub end_time: f64,    // i64 as a js number
+    pub no_hedging: bool,
+    pub min_wager: f64, // u64 as a js number, exact up to 2^53
+    pub max_wager: f64, // u64 as a js number, exact up to 2^53
+    pub voter_cap: f64, // u64 as a js number, exact up to 2^53
+    pub creator_fee_bps: u16,
+    pub protocol_fee_bps: u16,
+    pub outcome: u8,
+    pub dispute_window: u32,
+    pub dispute_state: u8,
 }
 
 #[wasm_bindgen]
 #[derive(Clone)]
 pub struct PollOption {
     text: String,
-    pub quantity: u32, // u64, https://caniuse.com/#feat=bigint
+    pub quantity: f64, // u64 as a js number, exact up to 2^53
+    pub page_count: u32,
     tally_key: Pubkey,
 }
 
-impl From<PollData<'_>> for Poll {
-    fn from(poll_data: PollData) -> Self {
-        Self {
+impl TryFrom<PollData<'_>> for Poll {
+    type Error = JsValue;
+
+    fn try_from(poll_data: PollData) -> Result<Self, JsValue> {
+        Ok(Self {
             creator_key: poll_data.creator_key.to_owned(),
-            header: from_utf8(poll_data.header).unwrap().to_string(),
+            resolver_key: if poll_data.has_resolver() {
+                Some(poll_data.resolver_key.to_owned())
+            } else {
+                None
+            },
+            challenger_key: Pubkey::new(&poll_data.dispute.challenger_key[..]),
+            quote: PollQuote::from(&poll_data),
+            header: decode_text(poll_data.header)?,
             option_a: PollOption {
-                text: from_utf8(poll_data.option_a.text).unwrap().to_string(),
-                quantity: u32::try_from(poll_data.option_a.quantity.to_owned()).unwrap(),
+                text: decode_text(poll_data.option_a.text)?,
+                quantity: poll_data.option_a.quantity.get() as f64,
+                page_count: poll_data.option_a.page_count.get(),
                 tally_key: poll_data.option_a.tally_key.to_owned(),
             },
             option_b: PollOption {
-                text: from_utf8(poll_data.option_b.text).unwrap().to_string(),
-                quantity: u32::try_from(poll_data.option_b.quantity.to_owned()).unwrap(),
+                text: decode_text(poll_data.option_b.text)?,
+                quantity: poll_data.option_b.quantity.get() as f64,
+                page_count: poll_data.option_b.page_count.get(),
                 tally_key: poll_data.option_b.tally_key.to_owned(),
             },
-            last_block: u32::try_from(poll_data.last_block.to_owned()).unwrap(),
-        }
+            first_block: poll_data.first_block as f64,
+            last_block: poll_data.last_block as f64,
+            end_time: poll_data.end_time as f64,
+            no_hedging: poll_data.no_hedging,
+            min_wager: poll_data.min_wager as f64,
+            max_wager: poll_data.max_wager as f64,
+            voter_cap: poll_data.voter_cap as f64,
+            creator_fee_bps: poll_data.creator_fee_bps,
+            protocol_fee_bps: poll_data.protocol_fee_bps,
+            outcome: *poll_data.outcome,
+            dispute_window: poll_data.dispute.window,
+            dispute_state: *poll_data.dispute.state,
+        })
     }
 }
 
 #[wasm_bindgen]
 impl Poll {
     #[wasm_bindgen(js_name = fromData)]
-    pub fn from_data(val: &mut [u8]) -> Self {
+    pub fn from_data(val: &mut [u8]) -> Result<Poll, JsValue> {
         console_error_panic_hook::set_once();
-        PollData::from_bytes(val).into()
+        PollData::from_bytes(val)
+            .map_err(decode_error)
+            .and_then(Poll::try_from)
+    }
+
+    #[wasm_bindgen(js_name = feeAdjustedPot)]
+    pub fn fee_adjusted_pot(&self) -> f64 {
+        self.quote.fee_adjusted_pot() as f64
+    }
+
+    /// Payout per unit wagered if the option wins, in basis points
+    #[wasm_bindgen(js_name = oddsBps)]
+    pub fn odds_bps(&self, option: u8) -> Option<f64> {
+        self.quote
+            .odds_bps(PollOutcome::from(option))
+            .map(|odds| odds as f64)
+    }
+
+    /// Amount a voter would receive after fees if they wagered now and the option wins
+    #[wasm_bindgen(js_name = quotePayout)]
+    pub fn quote_payout(&self, option: u8, existing_wager: f64, wager: f64) -> f64 {
+        let payout = self.quote.projected_payout(
+            PollOutcome::from(option),
+            existing_wager as u64,
+            wager as u64,
+        );
+        payout as f64
     }
 
     #[wasm_bindgen(method, getter, js_name = creatorKey)]
@@ -57,6 +120,25 @@ impl Poll {
         Uint8Array::from(&self.creator_key.as_ref()[..]).into()
     }
 
+    /// Account the protocol fee is paid to, the same for every poll
+    #[wasm_bindgen(method, getter, js_name = treasuryKey)]
+    pub fn treasury_key(&self) -> JsValue {
+        Uint8Array::from(&PROTOCOL_TREASURY[..]).into()
+    }
+
+    #[wasm_bindgen(method, getter, js_name = resolverKey)]
+    pub fn resolver_key(&self) -> JsValue {
+        match self.resolver_key {
+            Some(key) => Uint8Array::from(&key.as_ref()[..]).into(),
+            None => JsValue::NULL,
+        }
+    }
+
+    #[wasm_bindgen(method, getter, js_name = challengerKey)]
+    pub fn challenger_key(&self) -> JsValue {
+        Uint8Array::from(&self.challenger_key.as_ref()[..]).into()
+    }
+
     #[wasm_bindgen(method, getter)]
     pub fn header(&self) -> String {
         self.header.clone()
@@ -1,6 +1,6 @@
+use crate::decode_error;
 use alloc::boxed::Box;
 use alloc::vec::Vec;
-use core::convert::TryFrom;
 use js_sys::Uint8Array;
 use prediction_poll_data::TallyData;
 use solana_sdk::pubkey::Pubkey;
@@ -8,15 +8,22 @@ use wasm_bindgen::prelude::*;
 
 #[wasm_bindgen]
 pub struct Tally {
-    tallies: Vec<(Pubkey, u32)>, // u64, https://caniuse.com/#feat=bigint
+    poll_key: Pubkey,
+    pub option: u8,
+    next_tally: Option<Pubkey>,
+    tallies: Vec<(Pubkey, f64)>, // u64 as a js number, exact up to 2^53
+    claimed: Vec<bool>,
 }
 
 impl From<TallyData<'_>> for Tally {
     fn from(tally_data: TallyData) -> Self {
         Self {
-            tallies: tally_data
-                .iter()
-                .map(|(k, w)| (k, u32::try_from(w).unwrap()))
+            poll_key: tally_data.poll_key,
+            option: tally_data.option as u8,
+            next_tally: tally_data.next_tally(),
+            tallies: tally_data.iter().map(|(k, w)| (k, w as f64)).collect(),
+            claimed: (0..tally_data.len())
+                .map(|index| tally_data.is_claimed(index))
                 .collect(),
         }
     }
@@ -25,9 +32,24 @@ impl From<TallyData<'_>> for Tally {
 #[wasm_bindgen]
 impl Tally {
     #[wasm_bindgen(js_name = fromData)]
-    pub fn from_data(val: &mut [u8]) -> Self {
+    pub fn from_data(val: &mut [u8]) -> Result<Tally, JsValue> {
         console_error_panic_hook::set_once();
-        TallyData::from_bytes(val).into()
+        TallyData::from_bytes(val)
+            .map(Into::into)
+            .map_err(decode_error)
+    }
+
+    #[wasm_bindgen(method, getter, js_name = pollKey)]
+    pub fn poll_key(&self) -> JsValue {
+        Uint8Array::from(&self.poll_key.as_ref()[..]).into()
+    }
+
+    #[wasm_bindgen(method, getter, js_name = nextTally)]
+    pub fn next_tally(&self) -> JsValue {
+        match self.next_tally {
+            Some(key) => Uint8Array::from(&key.as_ref()[..]).into(),
+            None => JsValue::NULL,
+        }
     }
 
     #[wasm_bindgen(method, getter)]
@@ -41,8 +63,23 @@ impl Tally {
     }
 
     #[wasm_bindgen(method, getter)]
-    pub fn wagers(&self) -> Box<[u32]> {
+    pub fn wagers(&self) -> Box<[f64]> {
         let js_wagers: Vec<_> = self.tallies.iter().map(|(_, wager)| *wager).collect();
         js_wagers.into_boxed_slice()
     }
+
+    /// Total wagered by a voter on this page
+    #[wasm_bindgen(js_name = wagerOf)]
+    pub fn wager_of(&self, user_key: &[u8]) -> f64 {
+        let user_key = Pubkey::new(user_key);
+        self.tallies
+            .iter()
+            .find(|(key, _)| *key == user_key)
+            .map_or(0.0, |(_, wager)| *wager)
+    }
+
+    #[wasm_bindgen(js_name = isClaimed)]
+    pub fn is_claimed(&self, index: usize) -> bool {
+        self.claimed[index]
+    }
 }
@@ -30,10 +30,35 @@ const publicKeyLayout = (property: string = 'publicKey'): Object => {
   return BufferLayout.blob(32, property);
 };
 
-export const userAccountSize = 1 + 32; // 32 = size of a public key
+// Accounts start with a version tag and the layout version, accounts created
+// before layouts were versioned start straight with their fields
+const versionTag = Buffer.from('msgfeed');
+const headerSize = versionTag.length + 1;
+
+function accountFields(data: Buffer): Buffer {
+  return data.slice(0, versionTag.length).equals(versionTag)
+    ? data.slice(headerSize)
+    : data;
+}
+
+// Instructions start with the version tag, the instruction version and a tag.
+// The program also reads untagged data the way older clients sent it.
+const instructionVersion = 2;
+function instructionData(
+  tag: number,
+  payload: Buffer = Buffer.alloc(0),
+): Buffer {
+  return Buffer.concat([
+    versionTag,
+    Buffer.from([instructionVersion, tag]),
+    payload,
+  ]);
+}
+
+export const userAccountSize = headerSize + 1 + 32; // 32 = size of a public key
 export function messageAccountSize(text: string): number {
   const textBuffer = Buffer.from(text);
-  return 32 + 32 + 32 + textBuffer.length; // 32 = size of a public key
+  return headerSize + 32 + 32 + 32 + textBuffer.length; // 32 = size of a public key
 }
 
 async function createUserAccount(
@@ -66,6 +91,7 @@ async function createUserAccount(
   transaction.add({
     keys,
     programId,
+    data: instructionData(0),
   });
 
   return userAccount;
@@ -109,7 +135,9 @@ export async function userBanned(
     BufferLayout.u8('banned'),
     publicKeyLayout('creator'),
   ]);
-  const userAccountData = userAccountDataLayout.decode(accountInfo.data);
+  const userAccountData = userAccountDataLayout.decode(
+    accountFields(accountInfo.data),
+  );
 
   return userAccountData.banned !== 0;
 }
@@ -129,7 +157,9 @@ async function readMessage(
     publicKeyLayout('creator'),
     BufferLayout.cstr('text'),
   ]);
-  const messageAccountData = messageAccountDataLayout.decode(accountInfo.data);
+  const messageAccountData = messageAccountDataLayout.decode(
+    accountFields(accountInfo.data),
+  );
 
   return {
     nextMessage: new PublicKey(messageAccountData.nextMessage),
@@ -258,7 +288,7 @@ export async function postMessageWithProgramId(
   transaction.add({
     keys,
     programId,
-    data: textBuffer,
+    data: instructionData(1, textBuffer),
   });
   return await sendAndConfirmTransaction(
     connection,
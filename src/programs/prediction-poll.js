@@ -9,7 +9,15 @@ import {
 } from '@solana/web3.js';
 import type {TransactionSignature} from '@solana/web3.js';
 
-import {Clock, Collection, Command, InitPoll, Poll, Tally} from '../../wasm';
+import {
+  Clock,
+  Collection,
+  Command,
+  InitPoll,
+  Poll,
+  Tally,
+  pollErrorMessage,
+} from '../../wasm';
 
 /**
  * Refreshes a poll collection
@@ -50,6 +58,40 @@ export async function refreshPoll(
   return [poll, accountInfo.lamports, [tallyA, tallyB]];
 }
 
+/**
+ * Fetches every page of a tally, starting from the tally key of a poll option
+ */
+export async function getTallyPages(
+  connection: Connection,
+  tallyKey: PublicKey,
+): Promise<Array<[PublicKey, Tally]>> {
+  const pages = [];
+  let pageKey = tallyKey;
+  while (pageKey) {
+    const tallyInfo = await connection.getAccountInfo(pageKey);
+    const tally = Tally.fromData(tallyInfo.data);
+    pages.push([pageKey, tally]);
+    pageKey = tally.nextTally ? new PublicKey(tally.nextTally) : null;
+  }
+  return pages;
+}
+
+/**
+ * Optional poll settings
+ */
+export type PollOptions = {
+  creatorFeeBps?: number,
+  startSlot?: number,
+  endTime?: number,
+  noHedging?: boolean,
+  minWager?: number,
+  maxWager?: number,
+  voterCap?: number,
+  resolverKey?: PublicKey,
+  disputeWindow?: number,
+  authorityKey?: PublicKey,
+};
+
 /**
  * Creates a new poll with two options and a block timeout
  */
@@ -63,6 +105,7 @@ export async function createPoll(
   optionA: string,
   optionB: string,
   timeout: number,
+  options: PollOptions = {},
 ): Promise<[TransactionSignature, Account]> {
   const transaction = new Transaction();
 
@@ -84,7 +127,7 @@ export async function createPoll(
         fromPubkey: payerAccount.publicKey,
         newAccountPubkey: tallyAccount.publicKey,
         lamports: 2000,
-        space: 1000, // 30+ votes
+        space: 1000, // 20+ votes
         programId,
       }),
     );
@@ -104,7 +147,9 @@ export async function createPoll(
       },
     ],
     programId,
-    data: Command.initPoll(new InitPoll(header, optionA, optionB, timeout)),
+    data: Command.initPoll(
+      newInitPoll(header, optionA, optionB, timeout, options),
+    ),
   });
 
   const signature = await sendAndConfirmTransaction(
@@ -121,7 +166,55 @@ export async function createPoll(
 }
 
 /**
- * Submit a vote to a poll
+ * Builds the poll initialization data
+ */
+function newInitPoll(
+  header: string,
+  optionA: string,
+  optionB: string,
+  timeout: number,
+  options: PollOptions,
+): InitPoll {
+  const {resolverKey, authorityKey} = options;
+  if (resolverKey && !(authorityKey && options.disputeWindow)) {
+    throw new Error('A poll with a resolver needs a dispute authority and window');
+  }
+  const initPoll = new InitPoll(
+    header,
+    optionA,
+    optionB,
+    timeout,
+    options.creatorFeeBps || 0,
+    resolverKey ? resolverKey.toBuffer() : undefined,
+  );
+  if (options.startSlot) {
+    initPoll.setStartSlot(options.startSlot);
+  }
+  if (options.endTime) {
+    initPoll.setEndTime(options.endTime);
+  }
+  if (options.noHedging) {
+    initPoll.setNoHedging(true);
+  }
+  if (options.minWager || options.maxWager || options.voterCap) {
+    initPoll.setWagerLimits(
+      options.minWager || 0,
+      options.maxWager || 0,
+      options.voterCap || 0,
+    );
+  }
+  if (authorityKey) {
+    initPoll.setDisputeWindow(
+      options.disputeWindow || 0,
+      authorityKey.toBuffer(),
+    );
+  }
+  return initPoll;
+}
+
+/**
+ * Submit a vote to a poll, passing every page of the selected tally and, when
+ * the poll forbids hedging or caps voters, every page of the other tally
  */
 export async function vote(
   connection: Connection,
@@ -131,6 +224,24 @@ export async function vote(
   wager: number,
   tally: PublicKey,
 ): Promise<TransactionSignature> {
+  const pollInfo = await connection.getAccountInfo(poll);
+  const pollData = Poll.fromData(pollInfo.data);
+  const tallyKeyA = new PublicKey(pollData.optionA.tallyKey);
+  const tallyKeyB = new PublicKey(pollData.optionB.tallyKey);
+  const otherTally = tally.equals(tallyKeyA) ? tallyKeyB : tallyKeyA;
+
+  const pageKeys = (await getTallyPages(connection, tally))
+    .slice(1)
+    .map(([pubkey]) => ({pubkey, isSigner: false, isWritable: true}));
+  const otherPageKeys =
+    pollData.no_hedging || pollData.voter_cap > 0
+      ? (await getTallyPages(connection, otherTally)).map(([pubkey]) => ({
+          pubkey,
+          isSigner: false,
+          isWritable: false,
+        }))
+      : [];
+
   const transaction = new Transaction();
 
   const userAccount = new Account();
@@ -155,6 +266,8 @@ export async function vote(
         isSigner: false,
         isWritable: false,
       },
+      ...pageKeys,
+      ...otherPageKeys,
     ],
     programId,
     data: Command.submitVote(),
@@ -169,7 +282,8 @@ export async function vote(
 }
 
 /**
- * Submit a claim to an expired poll
+ * Submit a claim to an expired poll, paying out one winning tally page per
+ * transaction
  */
 export async function claim(
   connection: Connection,
@@ -178,33 +292,407 @@ export async function claim(
   pollKey: PublicKey,
   poll: Poll,
 ): Promise<TransactionSignature> {
-  const tallyKey =
-    poll.optionA.quantity > poll.optionB.quantity
-      ? new PublicKey(poll.optionA.tallyKey)
-      : new PublicKey(poll.optionB.tallyKey);
+  const clockKey = getSysvarClockPublicKey();
+  const pages = await getTallyPages(connection, getWinningTallyKey(poll));
+
+  let signature;
+  for (const [tallyKey, tally] of pages) {
+    const transaction = new Transaction();
+    const payoutKeys = tally.keys.map(k => {
+      const pubkey = new PublicKey(k);
+      return {pubkey, isSigner: false, isWritable: false};
+    });
+
+    transaction.add({
+      keys: [
+        {pubkey: pollKey, isSigner: false, isWritable: true},
+        {pubkey: tallyKey, isSigner: false, isWritable: true},
+        {pubkey: clockKey, isSigner: false, isWritable: false},
+        ...getFeeKeys(poll),
+        ...payoutKeys,
+      ],
+      programId,
+      data: Command.submitClaim(),
+    });
+
+    signature = await sendAndConfirmTransaction(
+      connection,
+      transaction,
+      payerAccount,
+    );
+  }
+  return signature;
+}
+
+/**
+ * Claim a single voter's share of an expired poll from the tally page that
+ * holds their wager
+ */
+export async function claimPayout(
+  connection: Connection,
+  programId: PublicKey,
+  payerAccount: Account,
+  pollKey: PublicKey,
+  poll: Poll,
+  tallyKey: PublicKey,
+): Promise<TransactionSignature> {
+  const transaction = new Transaction();
+  transaction.add({
+    keys: [
+      {pubkey: payerAccount.publicKey, isSigner: true, isWritable: true},
+      {pubkey: pollKey, isSigner: false, isWritable: true},
+      {pubkey: tallyKey, isSigner: false, isWritable: true},
+      {
+        pubkey: getSysvarClockPublicKey(),
+        isSigner: false,
+        isWritable: false,
+      },
+      ...getFeeKeys(poll),
+    ],
+    programId,
+    data: Command.claimPayout(),
+  });
+
+  return await sendAndConfirmTransaction(connection, transaction, payerAccount);
+}
+
+/**
+ * Submit the outcome of an expired poll as its resolver
+ */
+export async function resolvePoll(
+  connection: Connection,
+  programId: PublicKey,
+  resolverAccount: Account,
+  pollKey: PublicKey,
+  outcome: number,
+): Promise<TransactionSignature> {
+  const transaction = new Transaction();
+  transaction.add({
+    keys: [
+      {pubkey: resolverAccount.publicKey, isSigner: true, isWritable: false},
+      {pubkey: pollKey, isSigner: false, isWritable: true},
+      {
+        pubkey: getSysvarClockPublicKey(),
+        isSigner: false,
+        isWritable: false,
+      },
+    ],
+    programId,
+    data: Command.resolvePoll(outcome),
+  });
 
+  return await sendAndConfirmTransaction(
+    connection,
+    transaction,
+    resolverAccount,
+  );
+}
+
+/**
+ * Challenge the resolved outcome of a poll by staking lamports
+ */
+export async function challengeOutcome(
+  connection: Connection,
+  programId: PublicKey,
+  payerAccount: Account,
+  pollKey: PublicKey,
+  stake: number,
+): Promise<TransactionSignature> {
+  const transaction = new Transaction();
+
+  const userAccount = new Account();
+  transaction.add(
+    SystemProgram.createAccount({
+      fromPubkey: payerAccount.publicKey,
+      newAccountPubkey: userAccount.publicKey,
+      lamports: stake,
+      space: 0,
+      programId,
+    }),
+  );
+
+  transaction.add({
+    keys: [
+      {pubkey: userAccount.publicKey, isSigner: true, isWritable: true},
+      {pubkey: pollKey, isSigner: false, isWritable: true},
+      {pubkey: payerAccount.publicKey, isSigner: false, isWritable: false},
+      {
+        pubkey: getSysvarClockPublicKey(),
+        isSigner: false,
+        isWritable: false,
+      },
+    ],
+    programId,
+    data: Command.challengeOutcome(),
+  });
+
+  return await sendAndConfirmTransaction(
+    connection,
+    transaction,
+    payerAccount,
+    userAccount,
+  );
+}
+
+/**
+ * Settle a challenged poll outcome as the poll's dispute authority
+ */
+export async function settleDispute(
+  connection: Connection,
+  programId: PublicKey,
+  authorityAccount: Account,
+  pollKey: PublicKey,
+  poll: Poll,
+  outcome: number,
+): Promise<TransactionSignature> {
+  const transaction = new Transaction();
+  transaction.add({
+    keys: [
+      {pubkey: authorityAccount.publicKey, isSigner: true, isWritable: false},
+      {pubkey: pollKey, isSigner: false, isWritable: true},
+      {
+        pubkey: new PublicKey(poll.challengerKey),
+        isSigner: false,
+        isWritable: true,
+      },
+    ],
+    programId,
+    data: Command.settleDispute(outcome),
+  });
+
+  return await sendAndConfirmTransaction(
+    connection,
+    transaction,
+    authorityAccount,
+  );
+}
+
+/**
+ * Cancel a poll before it expires, its wagers are refunded with `refundWagers`
+ * afterwards. Polls stalled waiting on their resolver or dispute authority can
+ * be cancelled by anyone.
+ */
+export async function cancelPoll(
+  connection: Connection,
+  programId: PublicKey,
+  creatorAccount: Account,
+  pollKey: PublicKey,
+  poll: Poll,
+): Promise<TransactionSignature> {
+  const challengerKeys = [];
+  if (poll.disputeState === 1) {
+    // The stake of an unsettled challenge is refunded right away
+    challengerKeys.push({
+      pubkey: new PublicKey(poll.challengerKey),
+      isSigner: false,
+      isWritable: true,
+    });
+  }
+
+  const transaction = new Transaction();
+  transaction.add({
+    keys: [
+      {pubkey: creatorAccount.publicKey, isSigner: true, isWritable: false},
+      {pubkey: pollKey, isSigner: false, isWritable: true},
+      {
+        pubkey: getSysvarClockPublicKey(),
+        isSigner: false,
+        isWritable: false,
+      },
+      ...challengerKeys,
+    ],
+    programId,
+    data: Command.cancelPoll(),
+  });
+
+  return await sendAndConfirmTransaction(
+    connection,
+    transaction,
+    creatorAccount,
+  );
+}
+
+/**
+ * Refund the wagers of a cancelled poll, or of a finished poll nobody can
+ * claim, one tally page per transaction
+ */
+export async function refundWagers(
+  connection: Connection,
+  programId: PublicKey,
+  payerAccount: Account,
+  pollKey: PublicKey,
+  poll: Poll,
+): Promise<TransactionSignature> {
   const clockKey = getSysvarClockPublicKey();
-  const tallyAccount = await connection.getAccountInfo(tallyKey);
-  const tally = Tally.fromData(tallyAccount.data);
+  const pages = [
+    ...(await getTallyPages(connection, new PublicKey(poll.optionA.tallyKey))),
+    ...(await getTallyPages(connection, new PublicKey(poll.optionB.tallyKey))),
+  ];
+
+  let signature;
+  for (const [tallyKey, tally] of pages) {
+    const transaction = new Transaction();
+    const refundKeys = tally.keys.map(k => {
+      const pubkey = new PublicKey(k);
+      return {pubkey, isSigner: false, isWritable: true};
+    });
+
+    transaction.add({
+      keys: [
+        {pubkey: pollKey, isSigner: false, isWritable: true},
+        {pubkey: tallyKey, isSigner: false, isWritable: true},
+        {pubkey: clockKey, isSigner: false, isWritable: false},
+        ...refundKeys,
+      ],
+      programId,
+      data: Command.refundWagers(),
+    });
+
+    signature = await sendAndConfirmTransaction(
+      connection,
+      transaction,
+      payerAccount,
+    );
+  }
+  return signature;
+}
+
+/**
+ * Close a settled poll and return its rent to the creator, the wagers of a
+ * cancelled poll or of a poll nobody can claim must be refunded first
+ */
+export async function closePoll(
+  connection: Connection,
+  programId: PublicKey,
+  collectionKey: PublicKey,
+  creatorAccount: Account,
+  pollKey: PublicKey,
+  poll: Poll,
+): Promise<TransactionSignature> {
+  const pages = [
+    ...(await getTallyPages(connection, new PublicKey(poll.optionA.tallyKey))),
+    ...(await getTallyPages(connection, new PublicKey(poll.optionB.tallyKey))),
+  ];
+  const pageKeys = getOverflowPageKeys(pages);
 
   const transaction = new Transaction();
-  const payoutKeys = tally.keys.map(k => {
-    const pubkey = new PublicKey(k);
-    return {pubkey, isSigner: false, isWritable: false};
+  transaction.add({
+    keys: [
+      {pubkey: creatorAccount.publicKey, isSigner: true, isWritable: true},
+      {pubkey: pollKey, isSigner: false, isWritable: true},
+      {pubkey: collectionKey, isSigner: false, isWritable: true},
+      {
+        pubkey: new PublicKey(poll.optionA.tallyKey),
+        isSigner: false,
+        isWritable: true,
+      },
+      {
+        pubkey: new PublicKey(poll.optionB.tallyKey),
+        isSigner: false,
+        isWritable: true,
+      },
+      {
+        pubkey: getSysvarClockPublicKey(),
+        isSigner: false,
+        isWritable: false,
+      },
+      ...pageKeys,
+    ],
+    programId,
+    data: Command.closePoll(),
   });
 
+  return await sendAndConfirmTransaction(
+    connection,
+    transaction,
+    creatorAccount,
+  );
+}
+
+/**
+ * Link a new tally page to the full last page of a poll option
+ */
+export async function addTallyPage(
+  connection: Connection,
+  programId: PublicKey,
+  payerAccount: Account,
+  pollKey: PublicKey,
+  tallyKey: PublicKey,
+): Promise<[TransactionSignature, Account]> {
+  const pages = await getTallyPages(connection, tallyKey);
+  const [lastPageKey] = pages[pages.length - 1];
+
+  const transaction = new Transaction();
+  const pageAccount = new Account();
+  transaction.add(
+    SystemProgram.createAccount({
+      fromPubkey: payerAccount.publicKey,
+      newAccountPubkey: pageAccount.publicKey,
+      lamports: 2000,
+      space: 1000, // 20+ votes
+      programId,
+    }),
+  );
+
   transaction.add({
     keys: [
+      {pubkey: pageAccount.publicKey, isSigner: true, isWritable: true},
       {pubkey: pollKey, isSigner: false, isWritable: true},
-      {pubkey: tallyKey, isSigner: false, isWritable: false},
-      {pubkey: clockKey, isSigner: false, isWritable: false},
-      ...payoutKeys,
+      {pubkey: lastPageKey, isSigner: false, isWritable: true},
     ],
     programId,
-    data: Command.submitClaim(),
+    data: Command.addTallyPage(),
   });
 
-  return await sendAndConfirmTransaction(connection, transaction, payerAccount);
+  const signature = await sendAndConfirmTransaction(
+    connection,
+    transaction,
+    payerAccount,
+    pageAccount,
+  );
+
+  return [signature, pageAccount];
+}
+
+/**
+ * Pages after the first of each tally, in the order they were fetched
+ */
+function getOverflowPageKeys(pages: Array<[PublicKey, Tally]>) {
+  return pages
+    .filter(([, tally], index) => index > 0 && pages[index - 1][1].nextTally)
+    .map(([pubkey]) => ({pubkey, isSigner: false, isWritable: true}));
+}
+
+/**
+ * Tally of the resolved outcome, or of the most wagered option
+ */
+function getWinningTallyKey(poll: Poll): PublicKey {
+  if (poll.resolverKey) {
+    return poll.outcome === 1
+      ? new PublicKey(poll.optionA.tallyKey)
+      : new PublicKey(poll.optionB.tallyKey);
+  }
+  return poll.optionA.quantity > poll.optionB.quantity
+    ? new PublicKey(poll.optionA.tallyKey)
+    : new PublicKey(poll.optionB.tallyKey);
+}
+
+/**
+ * Accounts that receive the creator and protocol fees of a poll
+ */
+function getFeeKeys(poll: Poll) {
+  return [
+    {
+      pubkey: new PublicKey(poll.creatorKey),
+      isSigner: false,
+      isWritable: true,
+    },
+    {
+      pubkey: new PublicKey(poll.treasuryKey),
+      isSigner: false,
+      isWritable: true,
+    },
+  ];
 }
 
 /**
@@ -213,3 +701,11 @@ export async function claim(
 export function getSysvarClockPublicKey(): PublicKey {
   return new PublicKey('SysvarC1ock11111111111111111111111111111111');
 }
+
+/**
+ * Describes the prediction poll error that made a transaction fail, if any
+ */
+export function describeError(err: Error): ?string {
+  const match = /"CustomError":\s*(\d+)/.exec(err.message);
+  return match ? pollErrorMessage(parseInt(match[1], 10)) : null;
+}
@@ -5,6 +5,7 @@ import {
   createPoll,
   refreshPoll,
   claim,
+  describeError,
   vote,
 } from '../../programs/prediction-poll';
 
@@ -111,7 +112,7 @@ export default class PredictionPollApi {
     } catch (err) {
       console.error(`Failed to vote on poll: ${err}`);
       return {
-        snackMessage: 'An error occured when voting',
+        snackMessage: describeError(err) || 'An error occured when voting',
       };
     }
   }
@@ -133,7 +134,8 @@ export default class PredictionPollApi {
     } catch (err) {
       console.error(`Failed to submit claim: ${err}`);
       return {
-        snackMessage: 'An error occured when submitting claim',
+        snackMessage:
+          describeError(err) || 'An error occured when submitting claim',
       };
     }
   }
@@ -173,7 +175,8 @@ export default class PredictionPollApi {
     } catch (err) {
       console.error(`Failed to create poll: ${err}`);
       return {
-        snackMessage: 'An error occured when creating the poll',
+        snackMessage:
+          describeError(err) || 'An error occured when creating the poll',
       };
     } finally {
       this.creating = false;